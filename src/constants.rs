@@ -2,18 +2,24 @@ use millegrilles_common_rust::error::Error;
 
 pub const DOMAIN_NAME: &str = "DataCollector";
 pub const DOMAIN_DATASOURCEMAPPER: &str = "datasource_mapper";
+/// User account domain
+pub const DOMAIN_USER_ACCOUNTS: &str = "CoreMaitreDesComptes";
 
 pub const COLLECTION_NAME_TRANSACTIONS: &str = DOMAIN_NAME;
 pub const COLLECTION_NAME_FEEDS: &str = "DataCollector/feeds";
 pub const COLLECTION_NAME_FEED_VIEWS: &str = "DataCollector/feeds/views";
+pub const COLLECTION_NAME_FEED_STATUS: &str = "DataCollector/feeds/status";
 pub const COLLECTION_NAME_DATA_DATACOLLECTOR: &str = "DataCollector/data/DataCollector";
 pub const COLLECTION_NAME_FEED_VIEW_GROUPED_DATED: &str = "DataCollector/view/GroupedDated";
 pub const COLLECTION_NAME_FEED_VIEW_DATED: &str = "DataCollector/view/Dated";
+pub const COLLECTION_NAME_FEED_VIEW_KEYED: &str = "DataCollector/view/Keyed";
+pub const COLLECTION_NAME_FEED_VIEW_TIME_SERIES: &str = "DataCollector/view/TimeSeries";
 pub const COLLECTION_NAME_SRC_DATAFILES: &str = "DataCollector/source/DataFiles";
 pub const COLLECTION_NAME_SRC_FILES_VOLATILE: &str = "DataCollector/volatile/files";
 
 pub const REQUEST_GET_FEEDS: &str = "getFeeds";
 pub const REQUEST_GET_FEEDS_FOR_SCRAPER: &str = "getFeedsForScraper";
+pub const REQUEST_GET_DUE_FEEDS_FOR_SCRAPER: &str = "getDueFeedsForScraper";
 pub const REQUEST_GET_FEED_VIEWS: &str = "getFeedViews";
 pub const REQUEST_CHECK_EXISTING_DATA_IDS: &str = "checkExistingDataIds";
 pub const REQUEST_GET_DATA_ITEMS_MOST_RECENT: &str = "getDataItemsMostRecent";
@@ -21,10 +27,24 @@ pub const REQUEST_GET_DATA_ITEMS_DATE_RANGE: &str = "getDataItemsDateRange";
 pub const REQUEST_GET_FUUIDS_VOLATILE: &str = "getFuuidsVolatile";
 pub const REQUEST_GET_FEED_DATA: &str = "getFeedData";
 pub const REQUEST_GET_VIEW_DATA: &str = "getFeedViewData";
+pub const REQUEST_GET_DATA_ITEMS_V1_TO_MIGRATE: &str = "getDataItemsV1ToMigrate";
+pub const REQUEST_GET_FEED_STATUS: &str = "getFeedStatus";
+pub const REQUEST_GET_VIEW_AGGREGATES: &str = "getFeedViewAggregates";
+pub const REQUEST_GET_VIEW_GROUPS: &str = "getFeedViewGroups";
+pub const REQUEST_GET_VIEW_GROUPS_LATEST: &str = "getFeedViewGroupsLatest";
+pub const REQUEST_GET_FEED_TRANSFERS: &str = "getFeedTransfers";
 
 pub const COMMAND_ADD_FUUIDS_VOLATILE: &str = "addFuuidsVolatile";
 pub const COMMAND_PROCESS_VIEW: &str = "processView";
 pub const COMMAND_INSERT_VIEW_DATA: &str = "insertViewData";
+pub const COMMAND_ACK_FEED_POLL: &str = "ackFeedPoll";
+pub const COMMAND_LEASE_FEEDS: &str = "leaseFeeds";
+pub const COMMAND_RENEW_FEED_LEASES: &str = "renewFeedLeases";
+pub const COMMAND_RELEASE_FEED_LEASES: &str = "releaseFeedLeases";
+pub const COMMAND_REPORT_FEED_STATUS: &str = "reportFeedStatus";
+pub const COMMAND_PROCESS_VIEW_PROGRESS: &str = "processViewProgress";
+pub const COMMAND_PROCESS_VIEW_DONE: &str = "processViewDone";
+pub const COMMAND_PROCESS_VIEW_FAILED: &str = "processViewFailed";
 
 
 pub const TRANSACTION_CREATE_FEED: &str = "createFeed";
@@ -33,16 +53,88 @@ pub const TRANSACTION_DELETE_FEED: &str = "deleteFeed";
 pub const TRANSACTION_RESTORE_FEED: &str = "restoreFeed";
 pub const TRANSACTION_SAVE_DATA_ITEM: &str = "saveDataItem";
 pub const TRANSACTION_SAVE_DATA_ITEM_V2: &str = "saveDataItemV2";
+pub const TRANSACTION_SAVE_DATA_ITEMS_V2: &str = "saveDataItemsV2";
+pub const TRANSACTION_MIGRATE_DATA_ITEM_V1: &str = "migrateDataItemV1";
+pub const TRANSACTION_UPDATE_DATA_ITEM: &str = "updateDataItem";
+pub const TRANSACTION_DELETE_DATA_ITEMS: &str = "deleteDataItems";
 pub const TRANSACTION_CREATE_FEED_VIEW: &str = "createFeedView";
 pub const TRANSACTION_UPDATE_FEED_VIEW: &str = "updateFeedView";
+pub const TRANSACTION_DELETE_FEED_VIEW: &str = "deleteFeedView";
+pub const TRANSACTION_RESTORE_FEED_VIEW: &str = "restoreFeedView";
+pub const TRANSACTION_PURGE_FEED: &str = "purgeFeed";
+pub const TRANSACTION_EXPIRE_FEED_DATA: &str = "expireFeedData";
+pub const TRANSACTION_GRANT_FEED_ACCESS: &str = "grantFeedAccess";
+pub const TRANSACTION_REVOKE_FEED_ACCESS: &str = "revokeFeedAccess";
+pub const TRANSACTION_TRANSFER_FEED: &str = "transferFeed";
+pub const TRANSACTION_ACCEPT_FEED_TRANSFER: &str = "acceptFeedTransfer";
+pub const TRANSACTION_PURGE_USER_FEEDS: &str = "purgeUserFeeds";
+
+pub const EVENT_FEED_STALE: &str = "feedStale";
+pub const EVENT_FEED_TRANSFER_REQUESTED: &str = "feedTransferRequested";
+pub const EVENT_VIEW_PROCESSING_DONE: &str = "viewProcessingDone";
+pub const EVENT_VIEW_PROCESSING_FAILED: &str = "viewProcessingFailed";
+/// User account deleted, emitted by the user account domain.
+pub const EVENT_USER_ACCOUNT_DELETED: &str = "supprimerUsager";
+
+/// Env var to override the number of days a deleted feed is kept before being purged.
+pub const ENV_FEED_PURGE_GRACE_PERIOD_DAYS: &str = "DATACOLLECTOR_FEED_PURGE_DAYS";
+pub const FEED_PURGE_GRACE_PERIOD_DAYS: i64 = 30;
+
+/// Number of items removed at once when expiring feed data.
+pub const FEED_EXPIRE_BATCH_SIZE: i64 = 1000;
+/// Maximum number of batches expired by a single transaction, the next run continues.
+pub const FEED_EXPIRE_MAX_BATCHES: usize = 20;
+
+/// Env var to override the number of poll_rate periods without new data before a feed is stale.
+pub const ENV_FEED_STALE_POLLS: &str = "DATACOLLECTOR_FEED_STALE_POLLS";
+pub const FEED_STALE_POLLS: i64 = 10;
+
+/// Default duration of a scraper lease on feeds, in seconds.
+pub const FEED_LEASE_DURATION_SECS: i64 = 300;
+/// Bounds of the lease duration requested by a scraper, in seconds.
+pub const FEED_LEASE_MIN_DURATION_SECS: i64 = 30;
+pub const FEED_LEASE_MAX_DURATION_SECS: i64 = 1800;
+/// Default number of feeds leased at once by a scraper.
+pub const FEED_LEASE_MAX_FEEDS: usize = 10;
+/// Maximum number of feeds a scraper can lease at once.
+pub const FEED_LEASE_MAX_FEEDS_LIMIT: usize = 100;
+
+/// Views are processed once no new data was received on the feed for this delay, in seconds.
+pub const FEED_VIEW_PROCESSING_DEBOUNCE_SECS: i64 = 30;
+/// Maximum delay before processing new data on a view when the feed keeps receiving data, in seconds.
+pub const FEED_VIEW_PROCESSING_MAX_DELAY_SECS: i64 = 300;
+/// A view processing without completion after this delay is considered stuck and can be requested again, in seconds.
+pub const FEED_VIEW_PROCESSING_TIMEOUT_SECS: i64 = 3600;
+/// Maximum number of views requested for processing by each maintenance run, each request waits up to 5 seconds.
+/// Keeps the incremental and rebuild requests of a run within the one minute ticker.
+pub const FEED_VIEW_PROCESSING_MAX_VIEWS: i64 = 5;
+/// Number of view data rows of a replaced generation deleted per batch.
+pub const FEED_VIEW_PURGE_BATCH_SIZE: i64 = 1000;
+/// Maximum number of batches deleted for a view during a single maintenance run.
+pub const FEED_VIEW_PURGE_MAX_BATCHES: usize = 20;
+
+/// Number of scraper reports kept in the status history of a feed.
+pub const FEED_STATUS_HISTORY_SIZE: i32 = 20;
+
+/// Maximum number of buckets returned by a view aggregation request.
+pub const FEED_VIEW_AGGREGATES_MAX_BUCKETS: i64 = 5000;
+/// Maximum number of groups returned by a view latest items per group request.
+pub const FEED_VIEW_GROUPS_LATEST_MAX_GROUPS: i64 = 200;
+/// Maximum number of items per group returned by a view latest items per group request.
+pub const FEED_VIEW_GROUPS_LATEST_MAX_ITEMS: i64 = 20;
 
 /// Type of data, determines the collection and the unencrypted data elements that
 /// can be processed directly in the database without decryption.
+#[derive(Clone, Copy, PartialEq)]
 pub enum ViewDataType {
     /// Data with a pub_date (e.g. longitudinal, news article)
     Dated,
     /// Data with a pub_date and a group_id.
     GroupedDated,
+    /// Latest value for each group_id (e.g. sensor readings).
+    Keyed,
+    /// Numeric values with a plaintext timestamp, can be aggregated by the database.
+    TimeSeries,
 }
 
 impl TryFrom<&str> for ViewDataType {
@@ -51,9 +143,32 @@ impl TryFrom<&str> for ViewDataType {
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         let result = match value {
             "Dated" => Self::Dated,
-            "GrouepdDated" => Self::GroupedDated,
-            _ => Err("Unsupported type")?
+            "GroupedDated" => Self::GroupedDated,
+            "Keyed" => Self::Keyed,
+            "TimeSeries" => Self::TimeSeries,
+            _ => Err(format!("Unsupported view data type: {}", value))?
         };
         Ok(result)
     }
 }
+
+impl ViewDataType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Dated => "Dated",
+            Self::GroupedDated => "GroupedDated",
+            Self::Keyed => "Keyed",
+            Self::TimeSeries => "TimeSeries",
+        }
+    }
+
+    /// Name of the collection holding the view data of this type.
+    pub fn get_collection_name(&self) -> &'static str {
+        match self {
+            Self::Dated => COLLECTION_NAME_FEED_VIEW_DATED,
+            Self::GroupedDated => COLLECTION_NAME_FEED_VIEW_GROUPED_DATED,
+            Self::Keyed => COLLECTION_NAME_FEED_VIEW_KEYED,
+            Self::TimeSeries => COLLECTION_NAME_FEED_VIEW_TIME_SERIES,
+        }
+    }
+}
@@ -2,9 +2,11 @@ use serde::{Deserialize, Serialize};
 
 use millegrilles_common_rust::bson;
 use millegrilles_common_rust::chrono::{DateTime, Utc};
+use millegrilles_common_rust::error::Error as CommonError;
 use millegrilles_common_rust::millegrilles_cryptographie::chiffrage_docs::EncryptedDocument;
 use millegrilles_common_rust::mongo_dao::opt_chrono_datetime_as_bson_datetime;
-use crate::transactions_struct::{FeedViewGroupedDatedItem, FileItem};
+use crate::constants::ViewDataType;
+use crate::transactions_struct::{FeedAccessPermission, FeedAclEntry, FeedRetentionPolicy, FeedViewGroupedDatedItem, FileItem};
 
 #[derive(Serialize, Deserialize)]
 pub struct DataFeedRow {
@@ -38,6 +40,78 @@ pub struct DataFeedRow {
     pub deleted: bool,
     #[serde(default, with="opt_chrono_datetime_as_bson_datetime", skip_serializing_if = "Option::is_none")]
     pub deleted_at: Option<DateTime<Utc>>,
+    /// Limits on the data items kept for this feed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<FeedRetentionPolicy>,
+    /// Last poll acknowledged by a scraper.
+    #[serde(default, with="opt_chrono_datetime_as_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub last_polled_at: Option<DateTime<Utc>>,
+    /// Feed is due for polling after this date. Always due when None.
+    #[serde(default, with="opt_chrono_datetime_as_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub next_poll_at: Option<DateTime<Utc>>,
+    /// Common name of the scraper certificate holding the lease on this feed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lease_owner: Option<String>,
+    /// The lease can be reassigned after this date.
+    #[serde(default, with="opt_chrono_datetime_as_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub lease_expiration: Option<DateTime<Utc>>,
+    /// True when the feed produced no new data for too many poll periods.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale: Option<bool>,
+    /// Other users with access to this feed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acl: Option<Vec<FeedAclEntry>>,
+    /// User that must accept the transfer of the feed initiated by the owner.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transfer_user_id: Option<String>,
+}
+
+impl DataFeedRow {
+    /// True if the feed was shared with the user with the manage permission.
+    pub fn can_manage(&self, user_id: &str) -> bool {
+        match self.acl.as_ref() {
+            Some(acl) => acl.iter().any(|e| e.user_id == user_id && e.permission == FeedAccessPermission::Manage),
+            None => false
+        }
+    }
+}
+
+/// Poll result reported by a scraper.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FeedStatusReport {
+    #[serde(with="bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub date: DateTime<Utc>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    /// Number of items received during the poll.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item_count: Option<u64>,
+}
+
+/// Health of a feed, updated from the scraper reports.
+#[derive(Serialize, Deserialize)]
+pub struct FeedStatusRow {
+    pub feed_id: String,
+    #[serde(default, with="opt_chrono_datetime_as_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub last_attempt: Option<DateTime<Utc>>,
+    #[serde(default, with="opt_chrono_datetime_as_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub last_success: Option<DateTime<Utc>>,
+    /// Error of the last attempt, None when the last attempt succeeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_count: Option<u64>,
+    /// Number of failed attempts since the last success.
+    #[serde(default)]
+    pub consecutive_failures: u32,
+    /// Most recent reports, oldest first. Bounded to FEED_STATUS_HISTORY_SIZE.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<Vec<FeedStatusReport>>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -79,6 +153,14 @@ pub struct DataCollectorFilesRow {
     pub attached_fuuids: Option<Vec<String>>,
 }
 
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedViewProcessingStatus {
+    Processing,
+    Done,
+    Failed,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct FeedViewRow {
     pub feed_view_id: String,
@@ -95,6 +177,63 @@ pub struct FeedViewRow {
     pub modification_date: DateTime<Utc>,
     pub deleted: bool,
     pub ready: bool,
+    /// save_date of the most recent source data item requested for processing.
+    #[serde(default, with="opt_chrono_datetime_as_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<DateTime<Utc>>,
+    /// First new data received on the feed since the last processing request.
+    #[serde(default, with="opt_chrono_datetime_as_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub pending_since: Option<DateTime<Utc>>,
+    /// Last new data received on the feed since the last processing request.
+    #[serde(default, with="opt_chrono_datetime_as_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub pending_last: Option<DateTime<Utc>>,
+    /// Watermark before the current processing request, restored when the processing fails.
+    #[serde(default, with="opt_chrono_datetime_as_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub processing_since: Option<DateTime<Utc>>,
+    /// Upper bound (save_date) of the current incremental processing request.
+    #[serde(default, with="opt_chrono_datetime_as_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub processing_until: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub processing_status: Option<FeedViewProcessingStatus>,
+    #[serde(default, with="opt_chrono_datetime_as_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub processing_start_date: Option<DateTime<Utc>>,
+    #[serde(default, with="opt_chrono_datetime_as_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub processing_end_date: Option<DateTime<Utc>>,
+    /// Number of source items processed, reported by the datasource_mapper.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub processed_items: Option<u64>,
+    /// Number of source items to process, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_items: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_batch: Option<u32>,
+    /// Error of the last processing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub processing_error: Option<String>,
+    /// Generation of the view data shown to readers. None for data inserted before generations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<i64>,
+    /// Generation being rebuilt by a full processing, swapped in when the processing is done.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_generation: Option<i64>,
+    /// Highest generation allocated to the view, generations are never reused.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_generation: Option<i64>,
+    /// The view data of replaced or discarded generations must be removed by the maintenance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purge_generations: Option<bool>,
+    /// Source data was removed, the view must be rebuilt in a new generation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rebuild_pending: Option<bool>,
+}
+
+impl FeedViewRow {
+    /// Type of the view data, defaults to GroupedDated.
+    pub fn get_data_type(&self) -> Result<ViewDataType, CommonError> {
+        match self.data_type.as_ref() {
+            Some(data_type) => ViewDataType::try_from(data_type.as_str()),
+            None => Ok(ViewDataType::GroupedDated)
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -112,6 +251,12 @@ pub struct FeedViewGroupedDatedRow {
     pub group_id: Option<String>,
     /// Files associated with this data item
     pub files: Option<Vec<FileItem>>,
+    /// Plaintext content of the data item, decrypted views only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decrypted_data: Option<bson::Document>,
+    /// Generation of the view data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<i64>,
 }
 
 impl From<FeedViewGroupedDatedItem> for FeedViewGroupedDatedRow {
@@ -124,6 +269,8 @@ impl From<FeedViewGroupedDatedItem> for FeedViewGroupedDatedRow {
             encrypted_data: value.encrypted_data,
             group_id: value.group_id,
             files: value.files,
+            decrypted_data: value.decrypted_data,
+            generation: None,
         }
     }
 }
@@ -138,6 +285,67 @@ impl Into<FeedViewGroupedDatedItem> for FeedViewGroupedDatedRow {
             encrypted_data: self.encrypted_data,
             group_id: self.group_id,
             files: self.files,
+            decrypted_data: self.decrypted_data,
+            value: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FeedViewTimeSeriesRow {
+    /// Unique data item identifier for this feed view
+    pub data_id: String,
+    pub feed_view_id: String,
+    /// Source data of the data item
+    pub feed_id: String,
+    #[serde(with="bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub timestamp: DateTime<Utc>,
+    pub value: f64,
+    /// Series of the value, e.g. sensor identifier.
+    pub group_id: Option<String>,
+    pub encrypted_data: EncryptedDocument,
+    /// Generation of the view data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<i64>,
+}
+
+impl TryFrom<FeedViewGroupedDatedItem> for FeedViewTimeSeriesRow {
+    type Error = CommonError;
+
+    fn try_from(value: FeedViewGroupedDatedItem) -> Result<Self, Self::Error> {
+        let timestamp = match value.pub_date {
+            Some(inner) => inner,
+            None => Err(format!("Item {} has no pub_date", value.data_id))?
+        };
+        let item_value = match value.value {
+            Some(inner) => inner,
+            None => Err(format!("Item {} has no value", value.data_id))?
+        };
+        Ok(Self {
+            data_id: value.data_id,
+            feed_view_id: value.feed_view_id,
+            feed_id: value.feed_id,
+            timestamp,
+            value: item_value,
+            group_id: value.group_id,
+            encrypted_data: value.encrypted_data,
+            generation: None,
+        })
+    }
+}
+
+impl Into<FeedViewGroupedDatedItem> for FeedViewTimeSeriesRow {
+    fn into(self) -> FeedViewGroupedDatedItem {
+        FeedViewGroupedDatedItem {
+            data_id: self.data_id,
+            feed_view_id: self.feed_view_id,
+            feed_id: self.feed_id,
+            pub_date: Some(self.timestamp),
+            encrypted_data: self.encrypted_data,
+            group_id: self.group_id,
+            files: None,
+            decrypted_data: None,
+            value: Some(self.value),
         }
     }
 }
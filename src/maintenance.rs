@@ -1,6 +1,22 @@
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use millegrilles_common_rust::bson::{doc, oid::ObjectId};
+use millegrilles_common_rust::certificats::ValidateurX509;
+use millegrilles_common_rust::chrono::{DateTime, Duration, Utc};
+use millegrilles_common_rust::common_messages::parse_confirmation_response;
+use millegrilles_common_rust::constantes::Securite;
+use millegrilles_common_rust::error::Error as CommonError;
+use millegrilles_common_rust::generateur_messages::{GenerateurMessages, RoutageMessageAction};
+use millegrilles_common_rust::millegrilles_cryptographie::messages_structs::{epochmilliseconds, optionepochmilliseconds, optionepochseconds};
 use millegrilles_common_rust::tokio;
-use millegrilles_common_rust::middleware::Middleware;
+use millegrilles_common_rust::middleware::{sauvegarder_traiter_transaction_serializable_v2, Middleware};
+use millegrilles_common_rust::mongo_dao::{start_transaction_regular, MongoDao};
+use millegrilles_common_rust::mongodb::options::{CountOptions, FindOneOptions, FindOptions};
+
+use crate::constants::*;
+use crate::data_mongodb::{DataCollectorFilesRow, DataFeedRow, FeedViewProcessingStatus, FeedViewRow};
 use crate::domain_manager::DataCollectorDomainManager;
+use crate::transactions_struct::{ExpireFeedDataTransaction, PurgeFeedTransaction};
 
 pub async fn maintenance_thread<M>(_manager: &DataCollectorDomainManager, _middleware: &M)
     where M: Middleware
@@ -17,3 +33,535 @@ pub async fn maintenance_thread<M>(_manager: &DataCollectorDomainManager, _middl
         tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
     }
 }
+
+/// Number of days a deleted feed is kept (can be restored) before being purged.
+fn get_feed_purge_grace_period() -> Duration {
+    let days = match std::env::var(ENV_FEED_PURGE_GRACE_PERIOD_DAYS) {
+        Ok(value) => match value.parse::<i64>() {
+            Ok(days) => days,
+            Err(e) => {
+                warn!("get_feed_purge_grace_period Invalid value for {} ({:?}), using default", ENV_FEED_PURGE_GRACE_PERIOD_DAYS, e);
+                FEED_PURGE_GRACE_PERIOD_DAYS
+            }
+        },
+        Err(_) => FEED_PURGE_GRACE_PERIOD_DAYS
+    };
+    Duration::days(days)
+}
+
+/// Emits a purge transaction for each feed deleted for longer than the grace period.
+pub async fn purge_deleted_feeds<M>(manager: &DataCollectorDomainManager, middleware: &M) -> Result<(), CommonError>
+    where M: ValidateurX509 + GenerateurMessages + MongoDao
+{
+    let expiration = Utc::now() - get_feed_purge_grace_period();
+    debug!("purge_deleted_feeds Purging feeds deleted before {:?}", expiration);
+
+    let filtre = doc!{"deleted": true, "deleted_at": {"$lt": expiration}};
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let mut cursor = collection.find(filtre, None).await?;
+    let mut feed_ids = Vec::new();
+    while cursor.advance().await? {
+        let row = cursor.deserialize_current()?;
+        feed_ids.push(row.feed_id);
+    }
+
+    for feed_id in feed_ids {
+        info!("purge_deleted_feeds Purging feed {}", feed_id);
+        let transaction = PurgeFeedTransaction { feed_id };
+
+        let mut session = middleware.get_session().await?;
+        start_transaction_regular(&mut session).await?;
+        match sauvegarder_traiter_transaction_serializable_v2(
+            middleware, &transaction, manager, &mut session, DOMAIN_NAME, TRANSACTION_PURGE_FEED).await
+        {
+            Ok(_) => session.commit_transaction().await?,
+            Err(e) => {
+                warn!("purge_deleted_feeds Error purging feed {}: {:?}", transaction.feed_id, e);
+                session.abort_transaction().await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Emits an expiration transaction for each feed with data items outside of its retention policy.
+/// The files of expired items are released on the next claim of all files.
+pub async fn apply_retention_policies<M>(manager: &DataCollectorDomainManager, middleware: &M) -> Result<(), CommonError>
+    where M: ValidateurX509 + GenerateurMessages + MongoDao
+{
+    debug!("apply_retention_policies Start");
+    let now = Utc::now();
+
+    let filtre = doc!{"deleted": false, "retention": {"$ne": null}};
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let mut cursor = collection.find(filtre, None).await?;
+    let mut transactions = Vec::new();
+    while cursor.advance().await? {
+        let row = cursor.deserialize_current()?;
+        let retention = match row.retention {
+            Some(inner) => inner,
+            None => continue
+        };
+        let expiration_date = match retention.max_age_days {
+            Some(days) => Some(now - Duration::days(days as i64)),
+            None => None
+        };
+        let transaction = ExpireFeedDataTransaction { feed_id: row.feed_id, expiration_date, max_items: retention.max_items };
+        if has_expired_data(middleware, &transaction).await? {
+            transactions.push(transaction);
+        }
+    }
+
+    for transaction in transactions {
+        info!("apply_retention_policies Expiring data for feed {}", transaction.feed_id);
+        let mut session = middleware.get_session().await?;
+        start_transaction_regular(&mut session).await?;
+        match sauvegarder_traiter_transaction_serializable_v2(
+            middleware, &transaction, manager, &mut session, DOMAIN_NAME, TRANSACTION_EXPIRE_FEED_DATA).await
+        {
+            Ok(_) => session.commit_transaction().await?,
+            Err(e) => {
+                warn!("apply_retention_policies Error expiring data for feed {}: {:?}", transaction.feed_id, e);
+                session.abort_transaction().await?;
+            }
+        }
+    }
+
+    debug!("apply_retention_policies Done");
+    Ok(())
+}
+
+/// Avoids emitting transactions that would have no effect.
+async fn has_expired_data<M>(middleware: &M, transaction: &ExpireFeedDataTransaction) -> Result<bool, CommonError>
+    where M: MongoDao
+{
+    let collections = [
+        (COLLECTION_NAME_DATA_DATACOLLECTOR, "pub_date"),
+        (COLLECTION_NAME_SRC_DATAFILES, "save_date"),
+    ];
+
+    for (collection_name, date_field) in collections {
+        let collection = middleware.get_collection(collection_name)?;
+
+        if let Some(expiration_date) = transaction.expiration_date.as_ref() {
+            let filtre = doc!{"feed_id": &transaction.feed_id, date_field: {"$lt": expiration_date}};
+            let options = CountOptions::builder().limit(1).build();
+            if collection.count_documents(filtre, options).await? > 0 {
+                return Ok(true);
+            }
+        }
+
+        if let Some(max_items) = transaction.max_items {
+            let filtre = doc!{"feed_id": &transaction.feed_id};
+            let options = CountOptions::builder().limit(max_items.saturating_add(1)).build();
+            if collection.count_documents(filtre, options).await? > max_items {
+                return Ok(true);
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+/// Number of poll periods without new data before a feed is flagged stale.
+fn get_feed_stale_polls() -> i64 {
+    match std::env::var(ENV_FEED_STALE_POLLS) {
+        Ok(value) => match value.parse::<i64>() {
+            Ok(polls) => polls,
+            Err(e) => {
+                warn!("get_feed_stale_polls Invalid value for {} ({:?}), using default", ENV_FEED_STALE_POLLS, e);
+                FEED_STALE_POLLS
+            }
+        },
+        Err(_) => FEED_STALE_POLLS
+    }
+}
+
+#[derive(Serialize)]
+struct FeedStaleEvent {
+    feed_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_id: Option<String>,
+    /// Date of the most recent data item, None when the feed never produced data.
+    #[serde(with="optionepochseconds", skip_serializing_if = "Option::is_none")]
+    last_data_date: Option<DateTime<Utc>>,
+}
+
+/// Flags the active feeds that produced no new data for too many poll periods and emits a feedStale event.
+/// The flag is removed once new data is received.
+pub async fn detect_stale_feeds<M>(middleware: &M) -> Result<(), CommonError>
+    where M: GenerateurMessages + MongoDao
+{
+    debug!("detect_stale_feeds Start");
+    let now = Utc::now();
+    let stale_polls = get_feed_stale_polls();
+
+    let filtre = doc!{"deleted": false, "active": true, "poll_rate": {"$ne": null}};
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let mut cursor = collection.find(filtre, None).await?;
+    let mut feeds = Vec::new();
+    while cursor.advance().await? {
+        feeds.push(cursor.deserialize_current()?);
+    }
+
+    for feed in feeds {
+        let poll_rate = match feed.poll_rate {
+            Some(inner) => inner as i64,
+            None => continue
+        };
+
+        // Feeds still saving V1 items have no V2 save_date, the V1 pub_date is used as well.
+        let last_data_date = get_feed_latest_save_date(middleware, feed.feed_id.as_str()).await?;
+        let last_data_date_v1 = get_feed_latest_v1_pub_date(middleware, feed.feed_id.as_str()).await?;
+        let last_data_date = match (last_data_date, last_data_date_v1) {
+            (Some(date_v2), Some(date_v1)) => Some(date_v2.max(date_v1)),
+            (date_v2, date_v1) => date_v2.or(date_v1),
+        };
+
+        // Feeds that never produced data are measured from their creation.
+        let reference_date = last_data_date.unwrap_or(feed.created_at);
+        let stale = reference_date < now - Duration::seconds(poll_rate * stale_polls);
+        let was_stale = feed.stale.unwrap_or(false);
+        if stale == was_stale {
+            continue  // No change
+        }
+
+        let filtre = doc!{"feed_id": &feed.feed_id};
+        collection.update_one(filtre, doc!{"$set": {"stale": stale}}, None).await?;
+
+        if stale {
+            info!("detect_stale_feeds Feed {} is stale, last data {:?}", feed.feed_id, last_data_date);
+            let routage = RoutageMessageAction::builder(DOMAIN_NAME, EVENT_FEED_STALE, vec![Securite::L2Prive]).build();
+            let event = FeedStaleEvent { feed_id: feed.feed_id, user_id: feed.user_id, last_data_date };
+            middleware.emettre_evenement(routage, event).await?;
+        } else {
+            info!("detect_stale_feeds Feed {} is producing data again", feed.feed_id);
+        }
+    }
+
+    debug!("detect_stale_feeds Done");
+    Ok(())
+}
+
+/// Returns the save_date of the most recent source data item of the feed.
+pub async fn get_feed_latest_save_date<M>(middleware: &M, feed_id: &str) -> Result<Option<DateTime<Utc>>, CommonError>
+    where M: MongoDao
+{
+    let collection = middleware.get_collection_typed::<DataCollectorFilesRow>(COLLECTION_NAME_SRC_DATAFILES)?;
+    let options = FindOneOptions::builder().sort(doc!{"save_date": -1}).build();
+    match collection.find_one(doc!{"feed_id": feed_id}, options).await? {
+        Some(row) => Ok(Some(row.save_date)),
+        None => Ok(None)
+    }
+}
+
+/// Returns the pub_date of the most recent V1 data item of the feed.
+async fn get_feed_latest_v1_pub_date<M>(middleware: &M, feed_id: &str) -> Result<Option<DateTime<Utc>>, CommonError>
+    where M: MongoDao
+{
+    let collection = middleware.get_collection(COLLECTION_NAME_DATA_DATACOLLECTOR)?;
+    let options = FindOneOptions::builder()
+        .sort(doc!{"pub_date": -1})
+        .projection(doc!{"pub_date": 1})
+        .build();
+    match collection.find_one(doc!{"feed_id": feed_id}, options).await? {
+        Some(row) => match row.get_datetime("pub_date") {
+            Ok(pub_date) => Ok(Some(pub_date.to_chrono())),
+            Err(_) => Ok(None)
+        },
+        None => Ok(None)
+    }
+}
+
+#[derive(Serialize)]
+struct ProcessFeedViewIncrementalCommand<'a> {
+    feed_id: &'a str,
+    feed_view_id: &'a str,
+    /// Active generation of the view, None for data inserted before generations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    generation: Option<i64>,
+    /// Process the source data items with save_date > since. All items when None.
+    #[serde(with="optionepochmilliseconds", skip_serializing_if = "Option::is_none")]
+    since: Option<DateTime<Utc>>,
+    /// Process the source data items with save_date <= until.
+    #[serde(with="epochmilliseconds")]
+    until: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct ProcessFeedViewRebuildCommand<'a> {
+    feed_id: &'a str,
+    feed_view_id: &'a str,
+    /// Generation to write the view data into.
+    generation: i64,
+}
+
+/// Requests a full processing of the views flagged for rebuild after source data was removed.
+pub async fn rebuild_pending_feed_views<M>(middleware: &M) -> Result<(), CommonError>
+    where M: GenerateurMessages + MongoDao
+{
+    let now = Utc::now();
+    let filtre = doc!{
+        "active": true,
+        "deleted": false,
+        "rebuild_pending": true,
+        "$or": [
+            {"processing_status": {"$ne": "processing"}},
+            {"processing_start_date": {"$lte": now - Duration::seconds(FEED_VIEW_PROCESSING_TIMEOUT_SECS)}},
+        ]
+    };
+    // The remaining views are requested by the next runs.
+    let options = FindOptions::builder()
+        .sort(doc!{"modification_date": 1})
+        .limit(FEED_VIEW_PROCESSING_MAX_VIEWS)
+        .build();
+    let collection = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+    let mut cursor = collection.find(filtre, options).await?;
+    let mut views = Vec::new();
+    while cursor.advance().await? {
+        views.push(cursor.deserialize_current()?);
+    }
+
+    for view in views {
+        // Same as processView, the rebuild goes into a new generation. The data of a previous rebuild is purged.
+        let generation = view.last_generation.max(view.pending_generation).max(view.generation).unwrap_or(0) + 1;
+        let watermark = get_feed_latest_save_date(middleware, view.feed_id.as_str()).await?;
+
+        // Reserve the generation before the request, the datasource_mapper writes into it right away.
+        let mut set_ops = doc!{
+            "watermark": watermark, "pending_generation": generation, "last_generation": generation,
+            "processing_status": "processing", "processed_items": 0,
+        };
+        if view.pending_generation.is_some() {
+            set_ops.insert("purge_generations", true);
+        }
+        let ops = doc!{
+            "$set": set_ops,
+            "$unset": {
+                "rebuild_pending": true, "pending_since": true, "pending_last": true, "processing_since": true,
+                "processing_until": true, "processing_end_date": true, "total_items": true, "current_batch": true,
+                "processing_error": true,
+            },
+            "$currentDate": {"modification_date": true, "processing_start_date": true},
+        };
+        // Skip the view when it changed in the meantime
+        let filtre = doc!{"feed_view_id": &view.feed_view_id, "last_generation": view.last_generation, "pending_generation": view.pending_generation};
+        if collection.update_one(filtre, ops, None).await?.matched_count != 1 {
+            continue
+        }
+
+        debug!("rebuild_pending_feed_views Requesting rebuild of view {} in generation {}", view.feed_view_id, generation);
+        let command = ProcessFeedViewRebuildCommand {
+            feed_id: view.feed_id.as_str(),
+            feed_view_id: view.feed_view_id.as_str(),
+            generation,
+        };
+        let routage = RoutageMessageAction::builder(DOMAIN_DATASOURCEMAPPER, "processFeedView", vec![Securite::L3Protege])
+            .timeout_blocking(5_000)
+            .build();
+        let accepted = match middleware.transmettre_commande(routage, command).await? {
+            Some(response) => match parse_confirmation_response(&response) {
+                Some(confirmation) => Some(true) == confirmation.ok,
+                None => false
+            },
+            None => false
+        };
+        if !accepted {
+            warn!("rebuild_pending_feed_views Rebuild of view {} refused or no response, will retry", view.feed_view_id);
+            // Release the generation, the flag brings the view back on the next run
+            let filtre = doc!{"feed_view_id": &view.feed_view_id, "pending_generation": generation};
+            let ops = doc!{
+                "$set": {
+                    "rebuild_pending": true, "purge_generations": true, "watermark": view.watermark,
+                    "processing_status": "failed", "processing_error": "Rebuild refused or no response",
+                },
+                "$unset": {"pending_generation": true},
+                "$currentDate": {"modification_date": true, "processing_end_date": true},
+            };
+            collection.update_one(filtre, ops, None).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Requests incremental processing of the views with pending data from the datasource_mapper.
+/// A view is processed once its feed stopped receiving data for a moment, or after a maximum delay.
+pub async fn process_pending_feed_views<M>(middleware: &M) -> Result<(), CommonError>
+    where M: GenerateurMessages + MongoDao
+{
+    let now = Utc::now();
+    // Views being rebuilt get the new data once the rebuild is done.
+    // Views already processing wait for the end of the current request, unless it is stuck.
+    let filtre = doc!{
+        "active": true,
+        "deleted": false,
+        "pending_generation": null,
+        "rebuild_pending": {"$ne": true},  // The rebuild covers the new data
+        "$and": [
+            {"$or": [
+                {"pending_last": {"$lte": now - Duration::seconds(FEED_VIEW_PROCESSING_DEBOUNCE_SECS)}},
+                {"pending_since": {"$lte": now - Duration::seconds(FEED_VIEW_PROCESSING_MAX_DELAY_SECS)}},
+            ]},
+            {"$or": [
+                {"processing_status": {"$ne": "processing"}},
+                {"processing_start_date": {"$lte": now - Duration::seconds(FEED_VIEW_PROCESSING_TIMEOUT_SECS)}},
+            ]},
+        ]
+    };
+    // Oldest pending data first, the remaining views are requested by the next runs.
+    let options = FindOptions::builder()
+        .sort(doc!{"pending_since": 1})
+        .limit(FEED_VIEW_PROCESSING_MAX_VIEWS)
+        .build();
+    let collection = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+    let mut cursor = collection.find(filtre, options).await?;
+    let mut views = Vec::new();
+    while cursor.advance().await? {
+        views.push(cursor.deserialize_current()?);
+    }
+
+    for view in views {
+        let until = match get_feed_latest_save_date(middleware, view.feed_id.as_str()).await? {
+            Some(inner) => inner,
+            None => continue  // No data
+        };
+
+        // A stuck request is requested again from its own start, its data was never confirmed.
+        let since = match view.processing_status {
+            Some(FeedViewProcessingStatus::Processing) => {
+                warn!("process_pending_feed_views Processing of view {} timed out, requesting it again", view.feed_view_id);
+                view.processing_since
+            },
+            _ => view.watermark
+        };
+
+        if let Some(watermark) = since.as_ref() {
+            if *watermark >= until {
+                // Nothing new since the last request, clear the pending flag.
+                let filtre = doc!{"feed_view_id": &view.feed_view_id, "pending_last": view.pending_last};
+                collection.update_one(filtre, doc!{"$unset": {"pending_since": true, "pending_last": true}}, None).await?;
+                continue
+            }
+        }
+
+        debug!("process_pending_feed_views Requesting processing of view {} since {:?}", view.feed_view_id, since);
+        let command = ProcessFeedViewIncrementalCommand {
+            feed_id: view.feed_id.as_str(),
+            feed_view_id: view.feed_view_id.as_str(),
+            generation: view.generation,
+            since,
+            until,
+        };
+        let routage = RoutageMessageAction::builder(DOMAIN_DATASOURCEMAPPER, "processFeedView", vec![Securite::L3Protege])
+            .timeout_blocking(5_000)
+            .build();
+        let accepted = match middleware.transmettre_commande(routage, command).await? {
+            Some(response) => match parse_confirmation_response(&response) {
+                Some(confirmation) => Some(true) == confirmation.ok,
+                None => false
+            },
+            None => false
+        };
+        if !accepted {
+            warn!("process_pending_feed_views Processing of view {} refused or no response, will retry", view.feed_view_id);
+            continue
+        }
+
+        // The done/failed commands must match this request (since, until).
+        let filtre = doc!{"feed_view_id": &view.feed_view_id, "pending_generation": null};
+        let ops = doc!{
+            "$set": {
+                "watermark": until, "processing_since": since, "processing_until": until,
+                "processing_status": "processing", "processed_items": 0,
+            },
+            "$unset": {"processing_end_date": true, "total_items": true, "current_batch": true, "processing_error": true},
+            "$currentDate": {"processing_start_date": true},
+        };
+        collection.update_one(filtre, ops, None).await?;
+        // Keep the pending flag when new data was received in the meantime.
+        let filtre = doc!{"feed_view_id": &view.feed_view_id, "pending_last": view.pending_last};
+        collection.update_one(filtre, doc!{"$unset": {"pending_since": true, "pending_last": true}}, None).await?;
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct FeedViewDataRowId {
+    #[serde(rename="_id")]
+    id: ObjectId,
+}
+
+/// Removes the view data of the replaced and discarded generations in batches.
+/// Generations are never reused, the generations allocated after the view was loaded are kept.
+pub async fn purge_view_generations<M>(middleware: &M) -> Result<(), CommonError>
+    where M: MongoDao
+{
+    let collection = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+    let mut cursor = collection.find(doc!{"purge_generations": true}, None).await?;
+    let mut views = Vec::new();
+    while cursor.advance().await? {
+        views.push(cursor.deserialize_current()?);
+    }
+
+    for view in views {
+        let last_generation = match view.last_generation.max(view.pending_generation).max(view.generation) {
+            Some(inner) => inner,
+            None => {
+                // No generation was ever allocated, nothing to remove
+                let filtre = doc!{"feed_view_id": &view.feed_view_id, "last_generation": null, "pending_generation": null, "generation": null};
+                collection.update_one(filtre, doc!{"$unset": {"purge_generations": true}}, None).await?;
+                continue
+            }
+        };
+
+        let collection_name = view.get_data_type()?.get_collection_name();
+        let collection_data = middleware.get_collection(collection_name)?;
+        let collection_ids = middleware.get_collection_typed::<FeedViewDataRowId>(collection_name)?;
+        let filtre = doc!{
+            "feed_view_id": &view.feed_view_id,
+            "generation": {"$nin": [view.generation, view.pending_generation]},
+            "$or": [{"generation": null}, {"generation": {"$lte": last_generation}}],
+        };
+
+        let mut done = false;
+        let mut deleted_count = 0;
+        for _ in 0..FEED_VIEW_PURGE_MAX_BATCHES {
+            let options = FindOptions::builder()
+                .limit(FEED_VIEW_PURGE_BATCH_SIZE)
+                .projection(doc!{"_id": 1})
+                .build();
+            let mut cursor = collection_ids.find(filtre.clone(), options).await?;
+            let mut ids = Vec::new();
+            while cursor.advance().await? {
+                ids.push(cursor.deserialize_current()?.id);
+            }
+
+            let batch_full = ids.len() as i64 >= FEED_VIEW_PURGE_BATCH_SIZE;
+            if !ids.is_empty() {
+                let result = collection_data.delete_many(doc!{"_id": {"$in": ids}}, None).await?;
+                deleted_count += result.deleted_count;
+            }
+            if !batch_full {
+                done = true;
+                break
+            }
+        }
+        debug!("purge_view_generations Removed {} items of replaced generations of view {}", deleted_count, view.feed_view_id);
+
+        if done {
+            // Keep the flag when the generations changed in the meantime
+            let filtre = doc!{
+                "feed_view_id": &view.feed_view_id,
+                "generation": view.generation,
+                "pending_generation": view.pending_generation,
+                "last_generation": view.last_generation,
+            };
+            collection.update_one(filtre, doc!{"$unset": {"purge_generations": true}}, None).await?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use log::{debug, error, warn};
 use millegrilles_common_rust::bson::doc;
 use millegrilles_common_rust::certificats::{ValidateurX509, VerificateurPermissions};
@@ -6,7 +6,7 @@ use millegrilles_common_rust::chrono::{DateTime, Utc};
 use millegrilles_common_rust::common_messages::{parse_confirmation_response, verifier_reponse_ok, RequeteDechiffrageMessage};
 use millegrilles_common_rust::constantes::{RolesCertificats, Securite, DELEGATION_GLOBALE_PROPRIETAIRE};
 use millegrilles_common_rust::generateur_messages::{GenerateurMessages, RoutageMessageAction};
-use millegrilles_common_rust::millegrilles_cryptographie::messages_structs::{MessageMilleGrillesBufferDefault, optionepochseconds, RoutageMessage};
+use millegrilles_common_rust::millegrilles_cryptographie::messages_structs::{MessageMilleGrillesBufferDefault, optionepochmilliseconds, optionepochseconds, RoutageMessage};
 use millegrilles_common_rust::mongo_dao::{convertir_bson_deserializable, convertir_to_bson, start_transaction_regular, verifier_erreur_duplication_mongo, MongoDao};
 use millegrilles_common_rust::recepteur_messages::MessageValide;
 use millegrilles_common_rust::error::Error as CommonError;
@@ -19,10 +19,12 @@ use millegrilles_common_rust::{chrono, serde_json};
 use millegrilles_common_rust::mongodb::options::UpdateOptions;
 use crate::domain_manager::DataCollectorDomainManager;
 use crate::constants::*;
-use crate::data_mongodb::{DataCollectorRowIds, DataFeedRow, FeedViewGroupedDatedRow, FeedViewRow};
+use crate::data_mongodb::{DataCollectorRow, DataCollectorRowIds, DataFeedRow, FeedStatusReport, FeedViewGroupedDatedRow, FeedViewProcessingStatus, FeedViewRow, FeedViewTimeSeriesRow};
 use crate::file_maintenance::{claim_and_visit_files, claim_files};
 use crate::keymaster::{fetch_decryption_keys, transmit_attached_key};
-use crate::transactions_struct::{CreateFeedTransaction, CreateFeedViewTransaction, DeleteFeedTransaction, FeedViewGroupedDatedItem, FileItem, SaveDataItemTransaction, SaveDataItemTransactionV2, UpdateFeedTransaction, UpdateFeedViewTransaction};
+use crate::maintenance::get_feed_latest_save_date;
+use crate::messages_requests::get_feeds;
+use crate::transactions_struct::{AcceptFeedTransferTransaction, CreateFeedTransaction, CreateFeedViewTransaction, DeleteDataItem, DeleteFeedTransaction, DeleteFeedViewTransaction, FeedViewGroupedDatedItem, FileItem, GrantFeedAccessTransaction, RevokeFeedAccessTransaction, SaveDataItemTransaction, SaveDataItemTransactionV2, SaveDataItemsTransactionV2, TransferFeedTransaction, UpdateDataItem, UpdateFeedTransaction, UpdateFeedViewTransaction};
 
 pub async fn consume_command<M>(middleware: &M, message: MessageValide, manager: &DataCollectorDomainManager)
                                 -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
@@ -65,13 +67,31 @@ pub async fn consume_command<M>(middleware: &M, message: MessageValide, manager:
         TRANSACTION_UPDATE_FEED => command_update_feed(middleware, message, manager, &mut session).await,
         TRANSACTION_DELETE_FEED => command_delete_feed(middleware, message, manager, &mut session).await,
         TRANSACTION_RESTORE_FEED => command_restore_feed(middleware, message, manager, &mut session).await,
+        TRANSACTION_GRANT_FEED_ACCESS => command_grant_feed_access(middleware, message, manager, &mut session).await,
+        TRANSACTION_REVOKE_FEED_ACCESS => command_revoke_feed_access(middleware, message, manager, &mut session).await,
+        TRANSACTION_TRANSFER_FEED => command_transfer_feed(middleware, message, manager, &mut session).await,
+        TRANSACTION_ACCEPT_FEED_TRANSFER => command_accept_feed_transfer(middleware, message, manager, &mut session).await,
         TRANSACTION_SAVE_DATA_ITEM => command_save_data_item(middleware, message, manager, &mut session).await,
         TRANSACTION_SAVE_DATA_ITEM_V2 => command_save_data_item_v2(middleware, message, manager, &mut session).await,
+        TRANSACTION_SAVE_DATA_ITEMS_V2 => command_save_data_items_v2(middleware, message, manager, &mut session).await,
+        TRANSACTION_MIGRATE_DATA_ITEM_V1 => command_migrate_data_item_v1(middleware, message, manager, &mut session).await,
+        TRANSACTION_UPDATE_DATA_ITEM => command_update_data_item(middleware, message, manager, &mut session).await,
+        TRANSACTION_DELETE_DATA_ITEMS => command_delete_data_items(middleware, message, manager, &mut session).await,
         TRANSACTION_CREATE_FEED_VIEW => command_create_feed_view(middleware, message, manager, &mut session).await,
         TRANSACTION_UPDATE_FEED_VIEW => command_update_feed_view(middleware, message, manager, &mut session).await,
+        TRANSACTION_DELETE_FEED_VIEW | TRANSACTION_RESTORE_FEED_VIEW =>
+            command_delete_restore_feed_view(middleware, message, manager, &mut session).await,
         COMMAND_ADD_FUUIDS_VOLATILE => command_add_fuuids_volatile(middleware, message).await,
+        COMMAND_ACK_FEED_POLL => command_ack_feed_poll(middleware, message, &mut session).await,
+        COMMAND_LEASE_FEEDS => command_lease_feeds(middleware, message).await,
+        COMMAND_RENEW_FEED_LEASES => command_renew_feed_leases(middleware, message).await,
+        COMMAND_RELEASE_FEED_LEASES => command_release_feed_leases(middleware, message).await,
+        COMMAND_REPORT_FEED_STATUS => command_report_feed_status(middleware, message).await,
         COMMAND_PROCESS_VIEW => command_process_view(middleware, message, &mut session).await,
         COMMAND_INSERT_VIEW_DATA => command_insert_feed_view_data(middleware, message, &mut session).await,
+        COMMAND_PROCESS_VIEW_PROGRESS => command_process_view_progress(middleware, message).await,
+        COMMAND_PROCESS_VIEW_DONE => command_process_view_done(middleware, message, &mut session).await,
+        COMMAND_PROCESS_VIEW_FAILED => command_process_view_failed(middleware, message, &mut session).await,
         // Unknown command
         _ => {
             Ok(Some(middleware.reponse_err(Some(99), None, Some("Unknown command"))?))
@@ -176,10 +196,12 @@ where M: GenerateurMessages + MongoDao + ValidateurX509
         }
     };
 
-    if feed.user_id == Some(user_id) {
+    if feed.user_id.as_deref() == Some(user_id.as_str()) {
         // Ok, feed belongs to user
     } else if is_admin && feed.user_id.is_none() {
         // Ok, system feed managed by admin
+    } else if feed.can_manage(&user_id) {
+        // Ok, feed shared with the manage permission
     }  else {
         error!("command_update_feed Deleteing feed_id {} - user not authorized", command.feed_id);
         return Ok(Some(middleware.reponse_err(Some(401), None, Some("Unauthorized"))?));
@@ -300,6 +322,8 @@ where M: GenerateurMessages + MongoDao + ValidateurX509
         claim_and_visit_files(middleware, fuuids).await?;
     }
 
+    mark_feed_views_pending(middleware, transaction.feed_id.as_str()).await?;
+
     Ok(Some(middleware.reponse_ok(None, None)?))
 }
 
@@ -308,6 +332,32 @@ struct DataFeedUpdatedEvent {
     feed_id: String,
 }
 
+#[derive(Serialize)]
+struct DataFeedUpdatedCount {
+    feed_id: String,
+    /// Number of new items saved for the feed
+    count: usize,
+}
+
+/// feedDataUpdated event of a batch, lists all the feeds that received new data.
+#[derive(Serialize)]
+struct DataFeedsUpdatedEvent {
+    feeds: Vec<DataFeedUpdatedCount>,
+}
+
+/// Flags the active views of the feed for incremental processing of the new data.
+async fn mark_feed_views_pending<M>(middleware: &M, feed_id: &str) -> Result<(), CommonError>
+where M: MongoDao
+{
+    let now = Utc::now();
+    let filtre = doc!{"feed_id": feed_id, "active": true, "deleted": false};
+    // $min keeps the date of the first pending data
+    let ops = doc!{"$set": {"pending_last": now}, "$min": {"pending_since": now}};
+    let collection = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+    collection.update_many(filtre, ops, None).await?;
+    Ok(())
+}
+
 async fn command_save_data_item_v2<M>(middleware: &M, mut message: MessageValide, manager: &DataCollectorDomainManager, session: &mut ClientSession)
     -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
 where M: GenerateurMessages + MongoDao + ValidateurX509
@@ -366,198 +416,1410 @@ where M: GenerateurMessages + MongoDao + ValidateurX509
     debug!("command_save_data_item Claiming fuuids {:?}", fuuids_to_claim);
     claim_and_visit_files(middleware, fuuids_to_claim).await?;
 
+    mark_feed_views_pending(middleware, feed_id.as_str()).await?;
+
     let routage = RoutageMessageAction::builder(DOMAIN_NAME, "feedDataUpdated", vec![Securite::L3Protege]).build();
     middleware.emettre_evenement(routage, DataFeedUpdatedEvent {feed_id} ).await?;
 
     Ok(Some(middleware.reponse_ok(None, None)?))
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct FuuidVolatile {
-    pub correlation: String,
-    pub fuuid: String,
-    pub format: String,
-    pub cle_id: String,
-    pub nonce: Option<String>,
-    pub compression: Option<String>,
+#[derive(Serialize)]
+#[serde(rename_all="lowercase")]
+enum SaveDataItemStatus {
+    Ok,
+    Duplicate,
+    Error,
 }
 
-#[derive(Serialize, Deserialize)]
-struct CommandAddFuuidsVolatile {
-    files: Vec<FuuidVolatile>,
-    #[serde(default, with="optionepochseconds")]
-    expiration: Option<DateTime<Utc>>,
+#[derive(Serialize)]
+struct SaveDataItemResult {
+    data_id: String,
+    status: SaveDataItemStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    err: Option<String>,
 }
 
-async fn command_add_fuuids_volatile<M>(middleware: &M, mut message: MessageValide)
-                                        -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+#[derive(Serialize)]
+struct SaveDataItemsV2Response {
+    ok: bool,
+    items: Vec<SaveDataItemResult>,
+}
+
+async fn command_save_data_items_v2<M>(middleware: &M, mut message: MessageValide, manager: &DataCollectorDomainManager, session: &mut ClientSession)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
 where M: GenerateurMessages + MongoDao + ValidateurX509
 {
-    if !message.certificat.verifier_roles_string(vec!["web_scraper".to_string()])? {
+    if ! message.certificat.verifier_roles_string(vec!["web_scraper".to_string()])? {
         return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid role"))?));
-    } else if !message.certificat.verifier_exchanges(vec![Securite::L1Public])? {
+    } else if ! message.certificat.verifier_exchanges(vec![Securite::L1Public])? {
         return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid security level"))?));
     }
 
     let mut message_owned = message.message.parse_to_owned()?;
-    let command: CommandAddFuuidsVolatile = message_owned.deserialize()?;
+    let transaction: SaveDataItemsTransactionV2 = message_owned.deserialize()?;
 
-    let expiration = command.expiration.unwrap_or(Utc::now() + chrono::Duration::days(7));
-    for file in command.files {
-        let filtre = doc!{"correlation": &file.correlation};
-        let mut set_on_insert = convertir_to_bson(file)?;
-        set_on_insert.insert("expiration", &expiration);
-        set_on_insert.insert("created", Utc::now());
-        let ops = doc!{
-            "$setOnInsert": set_on_insert,
-            "$currentDate": {"modified": true}
+    // Load the feeds that can receive data, same rule as the transaction.
+    let feed_ids: HashSet<String> = transaction.items.iter().map(|item| item.feed_id.clone()).collect();
+    let filtre_feeds = doc!{"feed_id": {"$in": feed_ids.into_iter().collect::<Vec<String>>()}, "deleted": false};
+    let collection_feeds = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let mut cursor = collection_feeds.find(filtre_feeds, None).await?;
+    let mut valid_feed_ids = HashSet::new();
+    while cursor.advance().await? {
+        let row = cursor.deserialize_current()?;
+        valid_feed_ids.insert(row.feed_id);
+    }
+
+    // Check which data items already exist, all in one lookup.
+    // Same rule as saveDataItemV2: an item is a duplicate when (feed_id, data_id) exists.
+    let data_ids: Vec<String> = transaction.items.iter().map(|item| item.data_id.clone()).collect();
+    let filtre = doc!{"data_id": {"$in": data_ids}};
+    let collection = middleware.get_collection_typed::<DataCollectorRowIds>(COLLECTION_NAME_SRC_DATAFILES)?;
+    let mut cursor = collection.find(filtre, None).await?;
+    let mut existing_ids: HashMap<String, String> = HashMap::new();
+    while cursor.advance().await? {
+        let row = cursor.deserialize_current()?;
+        existing_ids.insert(row.data_id.to_string(), row.feed_id.to_string());
+    }
+
+    let mut results = Vec::with_capacity(transaction.items.len());
+    let mut accepted_items = Vec::new();
+    let mut fuuids_to_claim = Vec::new();
+    let mut updated_feeds: HashMap<String, usize> = HashMap::new();
+    for item in transaction.items {
+        let (status, err) = if ! valid_feed_ids.contains(&item.feed_id) {
+            (SaveDataItemStatus::Error, Some("Unknown feed".to_string()))
+        } else if let Some(feed_id) = existing_ids.get(&item.data_id) {
+            if feed_id == &item.feed_id {
+                // Already in the database or earlier in this batch
+                (SaveDataItemStatus::Duplicate, None)
+            } else {
+                // data_id is unique in the collection
+                (SaveDataItemStatus::Error, Some("data_id already used by another feed".to_string()))
+            }
+        } else {
+            existing_ids.insert(item.data_id.clone(), item.feed_id.clone());
+            fuuids_to_claim.push(item.data_fuuid.clone());
+            if let Some(files) = item.attached_fuuids.as_ref() {
+                fuuids_to_claim.extend(files.iter().cloned());
+            }
+            *updated_feeds.entry(item.feed_id.clone()).or_insert(0) += 1;
+            (SaveDataItemStatus::Ok, None)
         };
-        let collection = middleware.get_collection(COLLECTION_NAME_SRC_FILES_VOLATILE)?;
-        let options = UpdateOptions::builder().upsert(true).build();
-        collection.update_one(filtre, ops, options).await?;
+        let accepted = matches!(status, SaveDataItemStatus::Ok);
+        results.push(SaveDataItemResult { data_id: item.data_id.clone(), status, err });
+        if accepted {
+            accepted_items.push(item);
+        }
     }
 
-    Ok(Some(middleware.reponse_ok(None, None)?))
+    if updated_feeds.is_empty() {
+        // Nothing to save
+        return Ok(Some(middleware.build_reponse(SaveDataItemsV2Response { ok: true, items: results })?.0));
+    }
+
+    // Save the keys, either one key or a list of keys shared by the items.
+    let mut key_commands = Vec::new();
+    if let Some(mut attachements) = message_owned.attachements.take() {
+        if let Some(key) = attachements.remove("key") {
+            key_commands.push(key);
+        }
+        if let Some(serde_json::Value::Array(keys)) = attachements.remove("keys") {
+            key_commands.extend(keys);
+        }
+    }
+
+    for key in key_commands {
+        match transmit_attached_key(middleware, key).await {
+            Ok(Some(error)) => {
+                error!("command_save_data_items_v2 Invalid key content - command rejected");
+                return Ok(Some(error));
+            },
+            Err(e) => {
+                error!("command_save_data_items_v2 Error {:?} - command rejected", e);
+                return Ok(Some(middleware.reponse_err(Some(1), None, Some(format!("Error: {:?}", e).as_str()))?));
+            },
+            Ok(None) => ()  // Key saved successfully
+        }
+    }
+
+    // The transaction only contains the accepted items, rejected items are not applied on regeneration.
+    let transaction = SaveDataItemsTransactionV2 { items: accepted_items };
+    if let Err(e) = sauvegarder_traiter_transaction_serializable_v2(
+        middleware, &transaction, manager, session, DOMAIN_NAME, TRANSACTION_SAVE_DATA_ITEMS_V2).await
+    {
+        error!("command_save_data_items_v2 Error processing transaction - command rejected : {:?}", e);
+        return Ok(Some(middleware.reponse_err(Some(500), None, Some(format!("Error: {:?}", e).as_str()))?));
+    }
+
+    // Emit file claims for all saved items at once
+    debug!("command_save_data_items_v2 Claiming {} fuuids", fuuids_to_claim.len());
+    claim_and_visit_files(middleware, fuuids_to_claim).await?;
+
+    // A single event for all the feeds that received new data
+    let mut feeds = Vec::with_capacity(updated_feeds.len());
+    for (feed_id, count) in updated_feeds {
+        debug!("command_save_data_items_v2 Saved {} items for feed {}", count, feed_id);
+        mark_feed_views_pending(middleware, feed_id.as_str()).await?;
+        feeds.push(DataFeedUpdatedCount {feed_id, count});
+    }
+    let routage = RoutageMessageAction::builder(DOMAIN_NAME, "feedDataUpdated", vec![Securite::L3Protege]).build();
+    middleware.emettre_evenement(routage, DataFeedsUpdatedEvent {feeds} ).await?;
+
+    Ok(Some(middleware.build_reponse(SaveDataItemsV2Response { ok: true, items: results })?.0))
 }
 
-async fn command_create_feed_view<M>(middleware: &M, mut message: MessageValide, manager: &DataCollectorDomainManager, session: &mut ClientSession)
+async fn command_migrate_data_item_v1<M>(middleware: &M, mut message: MessageValide, manager: &DataCollectorDomainManager, session: &mut ClientSession)
     -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
-    where M: GenerateurMessages + MongoDao + ValidateurX509
+where M: GenerateurMessages + MongoDao + ValidateurX509
 {
+    // Migration is run by an administrator or a protected component.
+    let is_admin = message.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
+    let is_protected = message.certificat.verifier_exchanges(vec![Securite::L3Protege, Securite::L4Secure])?;
+    if ! is_admin && ! is_protected {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied"))?));
+    }
+
     let mut message_owned = message.message.parse_to_owned()?;
+    let transaction: SaveDataItemTransactionV2 = message_owned.deserialize()?;
 
-    let user_id = match message.certificat.get_user_id() {
-        Ok(inner) => match inner {
-            Some(user) => user.to_owned(),
-            None => {
-                error!("command_create_feed_view Invalid certificate, no user_id - command rejected");
-                return Ok(Some(middleware.reponse_err(Some(401), None, Some("Invalid certificate"))?));
-            }
-        },
-        Err(e) => Err(format!("command_create_feed_view Erreur get_user_id() : {:?}", e))?
+    // Load the V1 item being migrated
+    let collection_v1 = middleware.get_collection_typed::<DataCollectorRow>(COLLECTION_NAME_DATA_DATACOLLECTOR)?;
+    let filtre = doc!{"feed_id": &transaction.feed_id, "data_id": &transaction.data_id};
+    let row_v1 = match collection_v1.find_one(filtre, None).await? {
+        Some(inner) => inner,
+        None => return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown V1 data item"))?))
     };
-    let is_admin = message.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
 
-    // Deserialize to validate the format
-    let command: CreateFeedViewTransaction = message_owned.deserialize()?;
+    // The data_id of V2 items is unique across all feeds, the V1 item is kept on conflict.
+    let collection_v2 = middleware.get_collection(COLLECTION_NAME_SRC_DATAFILES)?;
+    if collection_v2.find_one(doc!{"data_id": &transaction.data_id}, None).await?.is_some() {
+        warn!("command_migrate_data_item_v1 V2 data item {} already exists - command rejected", transaction.data_id);
+        return Ok(Some(middleware.reponse_err(Some(409), None, Some("V2 data item already exists"))?));
+    }
 
-    // Check if the user is allowed to create a feed view on this feed
-    let filtre = doc!{"feed_id": &command.feed_id};
-    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
-    let feed = match collection.find_one(filtre, None).await? {
-        Some(feed) => feed,
-        None => {
-            error!("command_create_feed_view Unknown feed_id {} - command rejected", command.feed_id);
-            return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed"))?));
-        }
+    // The files of the V1 item must be kept as attached files to preserve the claims.
+    let attached_fuuids: HashSet<&String> = match transaction.attached_fuuids.as_ref() {
+        Some(inner) => inner.iter().collect(),
+        None => HashSet::new()
     };
-
-    if feed.user_id == Some(user_id) {
-        // Ok, feed belongs to user
-    } else if is_admin && feed.user_id.is_none() {
-        // Ok, system feed managed by admin
-    }  else {
-        error!("command_create_feed_view Feed_id {} - user not authorized", command.feed_id);
-        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Unauthorized"))?));
+    if let Some(files) = row_v1.files.as_ref() {
+        if files.iter().any(|file| ! attached_fuuids.contains(&file.fuuid)) {
+            return Ok(Some(middleware.reponse_err(Some(400), None, Some("Files of the V1 item missing from attached_fuuids"))?));
+        }
     }
 
-    // Save the key
+    let mut fuuids_to_claim = vec![transaction.data_fuuid.clone()];
+    fuuids_to_claim.extend(attached_fuuids.into_iter().cloned());
+
     let key_command = match message_owned.attachements {
         Some(mut inner) => inner.remove("key"),
         None => None
     };
 
-    match key_command {
-        Some(key) => {
-            match transmit_attached_key(middleware, key).await {
-                Ok(Some(error)) => {
-                    error!("command_create_feed Invalid key content - command rejected");
-                    return Ok(Some(error));
-                },
-                Err(e) => {
-                    error!("command_create_feed Error {:?} - command rejected", e);
-                    return Ok(Some(middleware.reponse_err(Some(1), None, Some(format!("Error: {:?}", e).as_str()))?));
-                },
-                Ok(None) => ()  // Key saved successfully
-            }
-        },
-        None => {
-            warn!("command_create_feed Encryption key is missing - command rejected");
-            return Ok(Some(middleware.reponse_err(Some(1), None, Some("Encryption key is missing"))?));
+    if let Some(key) = key_command {
+        match transmit_attached_key(middleware, key).await {
+            Ok(Some(error)) => {
+                error!("command_migrate_data_item_v1 Invalid key content - command rejected");
+                return Ok(Some(error));
+            },
+            Err(e) => {
+                error!("command_migrate_data_item_v1 Error {:?} - command rejected", e);
+                return Ok(Some(middleware.reponse_err(Some(1), None, Some(format!("Error: {:?}", e).as_str()))?));
+            },
+            Ok(None) => ()  // Key saved successfully
         }
     };
 
-    // Save and run new transaction
     if let Err(e) = sauvegarder_traiter_transaction_v2(middleware, message, manager, session).await {
-        warn!("command_create_feed Error in transaction processing - command rejected: {:?}", e);
-        return Ok(Some(middleware.reponse_err(Some(1), None, Some(e.to_string().as_str()))?));
+        error!("command_migrate_data_item_v1 Error processing transaction - command rejected : {:?}", e);
+        return Ok(Some(middleware.reponse_err(Some(500), None, Some(format!("Error: {:?}", e).as_str()))?));
     }
 
+    // Emit file claims
+    debug!("command_migrate_data_item_v1 Claiming fuuids {:?}", fuuids_to_claim);
+    claim_and_visit_files(middleware, fuuids_to_claim).await?;
+
+    mark_feed_views_pending(middleware, transaction.feed_id.as_str()).await?;
+
     Ok(Some(middleware.reponse_ok(None, None)?))
 }
 
-async fn command_update_feed_view<M>(middleware: &M, mut message: MessageValide, manager: &DataCollectorDomainManager, session: &mut ClientSession)
+async fn command_update_data_item<M>(middleware: &M, mut message: MessageValide, manager: &DataCollectorDomainManager, session: &mut ClientSession)
     -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
 where M: GenerateurMessages + MongoDao + ValidateurX509
 {
+    if ! message.certificat.verifier_roles_string(vec!["web_scraper".to_string()])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid role"))?));
+    } else if ! message.certificat.verifier_exchanges(vec![Securite::L1Public])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid security level"))?));
+    }
+
     let mut message_owned = message.message.parse_to_owned()?;
+    let transaction: UpdateDataItem = message_owned.deserialize()?;
+    let feed_id = transaction.feed_id.clone();
 
-    let user_id = match message.certificat.get_user_id() {
-        Ok(inner) => match inner {
-            Some(user) => user.to_owned(),
-            None => {
-                error!("command_update_feed_view Invalid certificate, no user_id - command rejected");
-                return Ok(Some(middleware.reponse_err(Some(401), None, Some("Invalid certificate"))?));
-            }
-        },
-        Err(e) => Err(format!("command_update_feed_view Erreur get_user_id() : {:?}", e))?
+    // Collect the new fuuids, including the replacement data file and added files.
+    let mut fuuids_to_claim = Vec::new();
+    if let Some(data_fuuid) = transaction.data_fuuid.as_ref() {
+        fuuids_to_claim.push(data_fuuid.clone());
+    }
+    if let Some(files) = transaction.add_files.as_ref() {
+        for file in files {
+            fuuids_to_claim.push(file.fuuid.clone());
+        }
+    }
+
+    // Check that the data item exists, V2 first
+    let filtre = doc!{"feed_id": &transaction.feed_id, "data_id": &transaction.data_id};
+    let collection_v2 = middleware.get_collection_typed::<DataCollectorRowIds>(COLLECTION_NAME_SRC_DATAFILES)?;
+    let is_v2 = collection_v2.find_one(filtre.clone(), None).await?.is_some();
+    if ! is_v2 {
+        let collection_v1 = middleware.get_collection_typed::<DataCollectorRowIds>(COLLECTION_NAME_DATA_DATACOLLECTOR)?;
+        if collection_v1.find_one(filtre, None).await?.is_none() {
+            return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown data item"))?));
+        }
+    }
+
+    // Reject the fields of the other item version, they would be ignored by the transaction.
+    let has_v1_fields = transaction.pub_date.is_some() || transaction.encrypted_content.is_some();
+    let has_v2_fields = transaction.data_fuuid.is_some() || transaction.key_ids.is_some() ||
+        transaction.pub_date_start.is_some() || transaction.pub_date_end.is_some();
+    if is_v2 && has_v1_fields {
+        return Ok(Some(middleware.reponse_err(Some(400), None, Some("pub_date and encrypted_content only apply to V1 data items"))?));
+    } else if !is_v2 && has_v2_fields {
+        return Ok(Some(middleware.reponse_err(Some(400), None, Some("data_fuuid, key_ids and pub_date_start/end only apply to V2 data items"))?));
+    }
+
+    let key_command = match message_owned.attachements {
+        Some(mut inner) => inner.remove("key"),
+        None => None
+    };
+
+    if let Some(key) = key_command {
+        match transmit_attached_key(middleware, key).await {
+            Ok(Some(error)) => {
+                error!("command_update_data_item Invalid key content - command rejected");
+                return Ok(Some(error));
+            },
+            Err(e) => {
+                error!("command_update_data_item Error {:?} - command rejected", e);
+                return Ok(Some(middleware.reponse_err(Some(1), None, Some(format!("Error: {:?}", e).as_str()))?));
+            },
+            Ok(None) => ()  // Key saved successfully
+        }
     };
+
+    if let Err(e) = sauvegarder_traiter_transaction_v2(middleware, message, manager, session).await {
+        error!("command_update_data_item Error processing transaction - command rejected : {:?}", e);
+        return Ok(Some(middleware.reponse_err(Some(500), None, Some(format!("Error: {:?}", e).as_str()))?));
+    }
+
+    if ! fuuids_to_claim.is_empty() {
+        // Emit file claims
+        debug!("command_update_data_item Claiming fuuids {:?}", fuuids_to_claim);
+        claim_and_visit_files(middleware, fuuids_to_claim).await?;
+    }
+
+    mark_feed_views_pending(middleware, feed_id.as_str()).await?;
+
+    if is_v2 {
+        let routage = RoutageMessageAction::builder(DOMAIN_NAME, "feedDataUpdated", vec![Securite::L3Protege]).build();
+        middleware.emettre_evenement(routage, DataFeedUpdatedEvent {feed_id} ).await?;
+    }
+
+    Ok(Some(middleware.reponse_ok(None, None)?))
+}
+
+async fn command_delete_data_items<M>(middleware: &M, mut message: MessageValide, manager: &DataCollectorDomainManager, session: &mut ClientSession)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    let message_owned = message.message.parse_to_owned()?;
+
+    let is_scraper = message.certificat.verifier_roles_string(vec!["web_scraper".to_string()])? &&
+        message.certificat.verifier_exchanges(vec![Securite::L1Public])?;
     let is_admin = message.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
 
     // Deserialize to validate the format
-    let command: UpdateFeedViewTransaction = message_owned.deserialize()?;
+    let command: DeleteDataItem = message_owned.deserialize()?;
 
-    // Check if the user is allowed to create a feed view on this feed
+    // Check if the user is allowed to delete data items from the feed
     let filtre = doc!{"feed_id": &command.feed_id};
     let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
     let feed = match collection.find_one(filtre, None).await? {
         Some(feed) => feed,
         None => {
-            error!("command_update_feed_view Unknown feed_id {} - command rejected", command.feed_id);
+            error!("command_delete_data_items Unknown feed_id {} - command rejected", command.feed_id);
             return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed"))?));
         }
     };
 
-    if feed.user_id == Some(user_id) {
-        // Ok, feed belongs to user
-    } else if is_admin && feed.user_id.is_none() {
-        // Ok, system feed managed by admin
-    }  else {
-        error!("command_update_feed_view Feed_id {} - user not authorized", command.feed_id);
-        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Unauthorized"))?));
+    if is_scraper {
+        // Ok, scraper manages the data of all feeds
+    } else {
+        let user_id = match message.certificat.get_user_id() {
+            Ok(inner) => match inner {
+                Some(user) => user.to_owned(),
+                None => {
+                    error!("command_delete_data_items Invalid certificate, no user_id - command rejected");
+                    return Ok(Some(middleware.reponse_err(Some(401), None, Some("Invalid certificate"))?));
+                }
+            },
+            Err(e) => Err(format!("command_delete_data_items Erreur get_user_id() : {:?}", e))?
+        };
+
+        if feed.user_id.as_deref() == Some(user_id.as_str()) {
+            // Ok, feed belongs to user
+        } else if is_admin && feed.user_id.is_none() {
+            // Ok, system feed managed by admin
+        } else if feed.can_manage(&user_id) {
+            // Ok, feed shared with the manage permission
+        }  else {
+            error!("command_delete_data_items Deleting data items of feed_id {} - user not authorized", command.feed_id);
+            return Ok(Some(middleware.reponse_err(Some(401), None, Some("Unauthorized"))?));
+        }
     }
 
-    // Save and run new transaction
-    if let Err(e) = sauvegarder_traiter_transaction_v2(middleware, message, manager, session).await {
-        warn!("command_update_feed_view Error in transaction processing - command rejected: {:?}", e);
-        return Ok(Some(middleware.reponse_err(Some(1), None, Some(e.to_string().as_str()))?));
+    if command.ids.is_empty() {
+        return Ok(Some(middleware.reponse_err(Some(400), None, Some("No data ids to delete"))?));
     }
 
+    // Save and run new transaction
+    sauvegarder_traiter_transaction_v2(middleware, message, manager, session).await?;
+
+    mark_feed_views_pending(middleware, command.feed_id.as_str()).await?;
+
     Ok(Some(middleware.reponse_ok(None, None)?))
 }
 
-#[derive(Deserialize)]
-struct ProcessViewRequest {
-    feed_view_id: String,
-}
+#[derive(Serialize, Deserialize)]
+pub struct FuuidVolatile {
+    pub correlation: String,
+    pub fuuid: String,
+    pub format: String,
+    pub cle_id: String,
+    pub nonce: Option<String>,
+    pub compression: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CommandAddFuuidsVolatile {
+    files: Vec<FuuidVolatile>,
+    #[serde(default, with="optionepochseconds")]
+    expiration: Option<DateTime<Utc>>,
+}
+
+async fn command_add_fuuids_volatile<M>(middleware: &M, mut message: MessageValide)
+                                        -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    if !message.certificat.verifier_roles_string(vec!["web_scraper".to_string()])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid role"))?));
+    } else if !message.certificat.verifier_exchanges(vec![Securite::L1Public])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid security level"))?));
+    }
+
+    let mut message_owned = message.message.parse_to_owned()?;
+    let command: CommandAddFuuidsVolatile = message_owned.deserialize()?;
+
+    let expiration = command.expiration.unwrap_or(Utc::now() + chrono::Duration::days(7));
+    for file in command.files {
+        let filtre = doc!{"correlation": &file.correlation};
+        let mut set_on_insert = convertir_to_bson(file)?;
+        set_on_insert.insert("expiration", &expiration);
+        set_on_insert.insert("created", Utc::now());
+        let ops = doc!{
+            "$setOnInsert": set_on_insert,
+            "$currentDate": {"modified": true}
+        };
+        let collection = middleware.get_collection(COLLECTION_NAME_SRC_FILES_VOLATILE)?;
+        let options = UpdateOptions::builder().upsert(true).build();
+        collection.update_one(filtre, ops, options).await?;
+    }
+
+    Ok(Some(middleware.reponse_ok(None, None)?))
+}
+
+#[derive(Deserialize)]
+struct CommandAckFeedPoll {
+    feed_id: String,
+}
+
+/// Scraper acknowledges a poll of the feed. Schedules the next poll using the poll_rate.
+async fn command_ack_feed_poll<M>(middleware: &M, mut message: MessageValide, session: &mut ClientSession)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    if !message.certificat.verifier_roles_string(vec!["web_scraper".to_string()])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid role"))?));
+    } else if !message.certificat.verifier_exchanges(vec![Securite::L1Public])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid security level"))?));
+    }
+
+    let mut message_owned = message.message.parse_to_owned()?;
+    let command: CommandAckFeedPoll = message_owned.deserialize()?;
+
+    let filtre = doc!{"feed_id": &command.feed_id, "deleted": false};
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let feed = match collection.find_one(filtre.clone(), None).await? {
+        Some(feed) => feed,
+        None => return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed"))?))
+    };
+
+    let now = Utc::now();
+    // Feeds without a poll_rate stay due.
+    let next_poll_at = match feed.poll_rate {
+        Some(poll_rate) => Some(now + chrono::Duration::seconds(poll_rate as i64)),
+        None => None
+    };
+    let ops = doc!{"$set": {"last_polled_at": now, "next_poll_at": next_poll_at}};
+    collection.update_one_with_session(filtre, ops, None, session).await?;
+
+    // The poll is done, release the lease held by this scraper.
+    let scraper_id = message.certificat.get_common_name()?;
+    let filtre_lease = doc!{"feed_id": &command.feed_id, "lease_owner": &scraper_id};
+    let ops = doc!{"$unset": {"lease_owner": true, "lease_expiration": true}};
+    collection.update_one_with_session(filtre_lease, ops, None, session).await?;
+
+    Ok(Some(middleware.reponse_ok(None, None)?))
+}
+
+#[derive(Deserialize)]
+struct CommandLeaseFeeds {
+    /// Maximum number of feeds to lease
+    max_feeds: Option<usize>,
+    /// Lease duration in seconds
+    duration: Option<i64>,
+}
+
+/// Leases due feeds to the scraper. Feeds leased by another scraper are skipped until the lease expires.
+async fn command_lease_feeds<M>(middleware: &M, mut message: MessageValide)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    if !message.certificat.verifier_roles_string(vec!["web_scraper".to_string()])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid role"))?));
+    } else if !message.certificat.verifier_exchanges(vec![Securite::L1Public])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid security level"))?));
+    }
+
+    let mut message_owned = message.message.parse_to_owned()?;
+    let command: CommandLeaseFeeds = message_owned.deserialize()?;
+
+    // Scraper instances are identified by the common name of their certificate.
+    let scraper_id = message.certificat.get_common_name()?;
+    // Bounded so that a single scraper cannot hold all the feeds.
+    let max_feeds = command.max_feeds.unwrap_or(FEED_LEASE_MAX_FEEDS).clamp(1, FEED_LEASE_MAX_FEEDS_LIMIT);
+    let duration = command.duration.unwrap_or(FEED_LEASE_DURATION_SECS).clamp(FEED_LEASE_MIN_DURATION_SECS, FEED_LEASE_MAX_DURATION_SECS);
+    let now = Utc::now();
+    let lease_expiration = now + chrono::Duration::seconds(duration);
+
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let mut leased_feed_ids: Vec<String> = Vec::with_capacity(max_feeds);
+    while leased_feed_ids.len() < max_feeds {
+        // Each feed is leased atomically, concurrent scrapers cannot get the same feed.
+        let filtre = doc!{
+            "deleted": false,
+            "active": true,
+            "feed_id": {"$nin": &leased_feed_ids},
+            "$and": [
+                {"$or": [{"next_poll_at": null}, {"next_poll_at": {"$lte": &now}}]},
+                {"$or": [{"lease_expiration": null}, {"lease_expiration": {"$lte": &now}}, {"lease_owner": &scraper_id}]},
+            ]
+        };
+        let ops = doc!{"$set": {"lease_owner": &scraper_id, "lease_expiration": &lease_expiration}};
+        match collection.find_one_and_update(filtre, ops, None).await? {
+            Some(feed) => leased_feed_ids.push(feed.feed_id),
+            None => break  // No more due feeds
+        }
+    }
+    debug!("command_lease_feeds Scraper {} leased {} feeds", scraper_id, leased_feed_ids.len());
+
+    let filtre = doc!{"feed_id": {"$in": leased_feed_ids}};
+    let response_message = get_feeds(middleware, &mut message, filtre).await?;
+
+    Ok(Some(middleware.build_reponse(response_message)?.0))
+}
+
+#[derive(Deserialize)]
+struct CommandFeedLeases {
+    feed_ids: Vec<String>,
+    /// Lease duration in seconds when renewing
+    duration: Option<i64>,
+}
+
+async fn command_renew_feed_leases<M>(middleware: &M, mut message: MessageValide)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    if !message.certificat.verifier_roles_string(vec!["web_scraper".to_string()])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid role"))?));
+    } else if !message.certificat.verifier_exchanges(vec![Securite::L1Public])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid security level"))?));
+    }
+
+    let mut message_owned = message.message.parse_to_owned()?;
+    let command: CommandFeedLeases = message_owned.deserialize()?;
+
+    // Only the leases still held by this scraper are renewed.
+    let scraper_id = message.certificat.get_common_name()?;
+    let duration = command.duration.unwrap_or(FEED_LEASE_DURATION_SECS).clamp(FEED_LEASE_MIN_DURATION_SECS, FEED_LEASE_MAX_DURATION_SECS);
+    let lease_expiration = Utc::now() + chrono::Duration::seconds(duration);
+    let filtre = doc!{"feed_id": {"$in": &command.feed_ids}, "lease_owner": &scraper_id};
+    let ops = doc!{"$set": {"lease_expiration": lease_expiration}};
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let result = collection.update_many(filtre, ops, None).await?;
+
+    if result.matched_count as usize != command.feed_ids.len() {
+        warn!("command_renew_feed_leases Scraper {} lost the lease on {} feeds", scraper_id, command.feed_ids.len() - result.matched_count as usize);
+        return Ok(Some(middleware.reponse_err(Some(409), None, Some("Lease lost on some feeds"))?));
+    }
+
+    Ok(Some(middleware.reponse_ok(None, None)?))
+}
+
+async fn command_release_feed_leases<M>(middleware: &M, mut message: MessageValide)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    if !message.certificat.verifier_roles_string(vec!["web_scraper".to_string()])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid role"))?));
+    } else if !message.certificat.verifier_exchanges(vec![Securite::L1Public])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid security level"))?));
+    }
+
+    let mut message_owned = message.message.parse_to_owned()?;
+    let command: CommandFeedLeases = message_owned.deserialize()?;
+
+    let scraper_id = message.certificat.get_common_name()?;
+    let filtre = doc!{"feed_id": {"$in": &command.feed_ids}, "lease_owner": &scraper_id};
+    let ops = doc!{"$unset": {"lease_owner": true, "lease_expiration": true}};
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    collection.update_many(filtre, ops, None).await?;
+
+    Ok(Some(middleware.reponse_ok(None, None)?))
+}
+
+#[derive(Deserialize)]
+struct CommandReportFeedStatus {
+    feed_id: String,
+    success: bool,
+    error: Option<String>,
+    http_status: Option<u16>,
+    item_count: Option<u64>,
+}
+
+/// Scraper reports the result of a poll on a feed.
+async fn command_report_feed_status<M>(middleware: &M, mut message: MessageValide)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    if !message.certificat.verifier_roles_string(vec!["web_scraper".to_string()])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid role"))?));
+    } else if !message.certificat.verifier_exchanges(vec![Securite::L1Public])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid security level"))?));
+    }
+
+    let mut message_owned = message.message.parse_to_owned()?;
+    let command: CommandReportFeedStatus = message_owned.deserialize()?;
+
+    let filtre = doc!{"feed_id": &command.feed_id, "deleted": false};
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    if collection.count_documents(filtre, None).await? == 0 {
+        return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed"))?));
+    }
+
+    let now = Utc::now();
+    let report = FeedStatusReport {
+        date: now,
+        success: command.success,
+        error: command.error,
+        http_status: command.http_status,
+        item_count: command.item_count,
+    };
+
+    let mut set_ops = doc!{
+        "last_attempt": &now,
+        "last_error": &report.error,
+        "http_status": report.http_status.map(|s| s as i32),
+        "item_count": report.item_count.map(|c| c as i64),
+    };
+    let mut ops = doc!{
+        // Only keep the most recent reports
+        "$push": {"history": {"$each": [convertir_to_bson(report.clone())?], "$slice": -FEED_STATUS_HISTORY_SIZE}},
+    };
+    if report.success {
+        set_ops.insert("last_success", &now);
+        set_ops.insert("consecutive_failures", 0);
+    } else {
+        ops.insert("$inc", doc!{"consecutive_failures": 1});
+    }
+    ops.insert("$set", set_ops);
+
+    let filtre = doc!{"feed_id": &command.feed_id};
+    let options = UpdateOptions::builder().upsert(true).build();
+    let collection = middleware.get_collection(COLLECTION_NAME_FEED_STATUS)?;
+    collection.update_one(filtre, ops, options).await?;
+
+    Ok(Some(middleware.reponse_ok(None, None)?))
+}
+
+async fn command_create_feed_view<M>(middleware: &M, mut message: MessageValide, manager: &DataCollectorDomainManager, session: &mut ClientSession)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+    where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    let mut message_owned = message.message.parse_to_owned()?;
+
+    let user_id = match message.certificat.get_user_id() {
+        Ok(inner) => match inner {
+            Some(user) => user.to_owned(),
+            None => {
+                error!("command_create_feed_view Invalid certificate, no user_id - command rejected");
+                return Ok(Some(middleware.reponse_err(Some(401), None, Some("Invalid certificate"))?));
+            }
+        },
+        Err(e) => Err(format!("command_create_feed_view Erreur get_user_id() : {:?}", e))?
+    };
+    let is_admin = message.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
+
+    // Deserialize to validate the format
+    let command: CreateFeedViewTransaction = message_owned.deserialize()?;
+
+    if let Some(data_type) = command.data_type.as_ref() {
+        if let Err(e) = ViewDataType::try_from(data_type.as_str()) {
+            error!("command_create_feed_view Invalid data_type {} - command rejected: {:?}", data_type, e);
+            return Ok(Some(middleware.reponse_err(Some(400), None, Some("Invalid data_type"))?));
+        }
+    }
+
+    // Check if the user is allowed to create a feed view on this feed
+    let filtre = doc!{"feed_id": &command.feed_id};
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let feed = match collection.find_one(filtre, None).await? {
+        Some(feed) => feed,
+        None => {
+            error!("command_create_feed_view Unknown feed_id {} - command rejected", command.feed_id);
+            return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed"))?));
+        }
+    };
+
+    if feed.user_id.as_deref() == Some(user_id.as_str()) {
+        // Ok, feed belongs to user
+    } else if is_admin && feed.user_id.is_none() {
+        // Ok, system feed managed by admin
+    } else if feed.can_manage(&user_id) {
+        // Ok, feed shared with the manage permission
+    }  else {
+        error!("command_create_feed_view Feed_id {} - user not authorized", command.feed_id);
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Unauthorized"))?));
+    }
+
+    // Save the key
+    let key_command = match message_owned.attachements {
+        Some(mut inner) => inner.remove("key"),
+        None => None
+    };
+
+    match key_command {
+        Some(key) => {
+            match transmit_attached_key(middleware, key).await {
+                Ok(Some(error)) => {
+                    error!("command_create_feed Invalid key content - command rejected");
+                    return Ok(Some(error));
+                },
+                Err(e) => {
+                    error!("command_create_feed Error {:?} - command rejected", e);
+                    return Ok(Some(middleware.reponse_err(Some(1), None, Some(format!("Error: {:?}", e).as_str()))?));
+                },
+                Ok(None) => ()  // Key saved successfully
+            }
+        },
+        None => {
+            warn!("command_create_feed Encryption key is missing - command rejected");
+            return Ok(Some(middleware.reponse_err(Some(1), None, Some("Encryption key is missing"))?));
+        }
+    };
+
+    // Save and run new transaction
+    if let Err(e) = sauvegarder_traiter_transaction_v2(middleware, message, manager, session).await {
+        warn!("command_create_feed Error in transaction processing - command rejected: {:?}", e);
+        return Ok(Some(middleware.reponse_err(Some(1), None, Some(e.to_string().as_str()))?));
+    }
+
+    Ok(Some(middleware.reponse_ok(None, None)?))
+}
+
+async fn command_update_feed_view<M>(middleware: &M, mut message: MessageValide, manager: &DataCollectorDomainManager, session: &mut ClientSession)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    let mut message_owned = message.message.parse_to_owned()?;
+
+    let user_id = match message.certificat.get_user_id() {
+        Ok(inner) => match inner {
+            Some(user) => user.to_owned(),
+            None => {
+                error!("command_update_feed_view Invalid certificate, no user_id - command rejected");
+                return Ok(Some(middleware.reponse_err(Some(401), None, Some("Invalid certificate"))?));
+            }
+        },
+        Err(e) => Err(format!("command_update_feed_view Erreur get_user_id() : {:?}", e))?
+    };
+    let is_admin = message.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
+
+    // Deserialize to validate the format
+    let command: UpdateFeedViewTransaction = message_owned.deserialize()?;
+
+    if let Some(data_type) = command.data_type.as_ref() {
+        if let Err(e) = ViewDataType::try_from(data_type.as_str()) {
+            error!("command_update_feed_view Invalid data_type {} - command rejected: {:?}", data_type, e);
+            return Ok(Some(middleware.reponse_err(Some(400), None, Some("Invalid data_type"))?));
+        }
+    }
+
+    // Check if the user is allowed to create a feed view on this feed
+    let filtre = doc!{"feed_id": &command.feed_id};
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let feed = match collection.find_one(filtre, None).await? {
+        Some(feed) => feed,
+        None => {
+            error!("command_update_feed_view Unknown feed_id {} - command rejected", command.feed_id);
+            return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed"))?));
+        }
+    };
+
+    if feed.user_id.as_deref() == Some(user_id.as_str()) {
+        // Ok, feed belongs to user
+    } else if is_admin && feed.user_id.is_none() {
+        // Ok, system feed managed by admin
+    } else if feed.can_manage(&user_id) {
+        // Ok, feed shared with the manage permission
+    }  else {
+        error!("command_update_feed_view Feed_id {} - user not authorized", command.feed_id);
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Unauthorized"))?));
+    }
+
+    // Save and run new transaction
+    if let Err(e) = sauvegarder_traiter_transaction_v2(middleware, message, manager, session).await {
+        warn!("command_update_feed_view Error in transaction processing - command rejected: {:?}", e);
+        return Ok(Some(middleware.reponse_err(Some(1), None, Some(e.to_string().as_str()))?));
+    }
+
+    Ok(Some(middleware.reponse_ok(None, None)?))
+}
+
+/// Handles deleteFeedView and restoreFeedView, both have the same access rules.
+async fn command_delete_restore_feed_view<M>(middleware: &M, mut message: MessageValide, manager: &DataCollectorDomainManager, session: &mut ClientSession)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    let mut message_owned = message.message.parse_to_owned()?;
+
+    let user_id = match message.certificat.get_user_id() {
+        Ok(inner) => match inner {
+            Some(user) => user.to_owned(),
+            None => {
+                error!("command_delete_restore_feed_view Invalid certificate, no user_id - command rejected");
+                return Ok(Some(middleware.reponse_err(Some(401), None, Some("Invalid certificate"))?));
+            }
+        },
+        Err(e) => Err(format!("command_delete_restore_feed_view Erreur get_user_id() : {:?}", e))?
+    };
+    let is_admin = message.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
+
+    // Deserialize to validate the format
+    let command: DeleteFeedViewTransaction = message_owned.deserialize()?;
+
+    // Check if the user is allowed to manage the views of this feed
+    let filtre = doc!{"feed_id": &command.feed_id};
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let feed = match collection.find_one(filtre, None).await? {
+        Some(feed) => feed,
+        None => {
+            error!("command_delete_restore_feed_view Unknown feed_id {} - command rejected", command.feed_id);
+            return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed"))?));
+        }
+    };
+
+    if feed.user_id.as_deref() == Some(user_id.as_str()) {
+        // Ok, feed belongs to user
+    } else if is_admin && feed.user_id.is_none() {
+        // Ok, system feed managed by admin
+    } else if feed.can_manage(&user_id) {
+        // Ok, feed shared with the manage permission
+    }  else {
+        error!("command_delete_restore_feed_view Feed_id {} - user not authorized", command.feed_id);
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Unauthorized"))?));
+    }
+
+    let filtre_view = doc!{"feed_view_id": &command.feed_view_id, "feed_id": &command.feed_id};
+    let collection_views = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+    if collection_views.find_one(filtre_view, None).await?.is_none() {
+        error!("command_delete_restore_feed_view Unknown feed_view_id {} - command rejected", command.feed_view_id);
+        return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed view"))?));
+    }
+
+    // Save and run new transaction
+    if let Err(e) = sauvegarder_traiter_transaction_v2(middleware, message, manager, session).await {
+        warn!("command_delete_restore_feed_view Error in transaction processing - command rejected: {:?}", e);
+        return Ok(Some(middleware.reponse_err(Some(1), None, Some(e.to_string().as_str()))?));
+    }
+
+    Ok(Some(middleware.reponse_ok(None, None)?))
+}
+
+#[derive(Deserialize)]
+struct ProcessViewRequest {
+    feed_view_id: String,
+}
+
+#[derive(Serialize)]
+struct ProcessStartEvent {
+    feed_id: String,
+    feed_view_id: String,
+    /// Generation to write the view data into.
+    generation: i64,
+}
+
+async fn command_process_view<M>(middleware: &M, mut message: MessageValide, session: &mut ClientSession)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    let mut message_owned = message.message.parse_to_owned()?;
+
+    let user_id = match message.certificat.get_user_id() {
+        Ok(inner) => match inner {
+            Some(user) => user.to_owned(),
+            None => {
+                error!("command_process_view Invalid certificate, no user_id - command rejected");
+                return Ok(Some(middleware.reponse_err(Some(401), None, Some("Invalid certificate"))?));
+            }
+        },
+        Err(e) => Err(format!("command_update_feed_view Erreur get_user_id() : {:?}", e))?
+    };
+    let is_admin = message.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
+
+    // Deserialize to validate the format
+    let command: ProcessViewRequest = message_owned.deserialize()?;
+
+    // Check if the user is allowed to create a feed view on this feed
+    let filtre_view = doc!{"feed_view_id": &command.feed_view_id};
+    let collection_feed_view = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+    let feed_view = match collection_feed_view.find_one(filtre_view.clone(), None).await? {
+        Some(inner) => inner,
+        None => {
+            error!("command_process_view No feed view with the provided Id");
+            return Ok(Some(middleware.reponse_err(Some(404), None, Some("No such feed view"))?));
+        }
+    };
+
+    let feed_id = &feed_view.feed_id;
+    let filtre_feed = doc!{"feed_id": feed_id};
+    let collection_feed = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let feed = match collection_feed.find_one(filtre_feed, None).await? {
+        Some(feed) => feed,
+        None => {
+            error!("command_process_view Unknown feed_id {} - command rejected", feed_id);
+            return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed"))?));
+        }
+    };
+
+    if feed.user_id.as_deref() == Some(user_id.as_str()) {
+        // Ok, feed belongs to user
+    } else if is_admin && feed.user_id.is_none() {
+        // Ok, system feed managed by admin
+    } else if feed.can_manage(&user_id) {
+        // Ok, feed shared with the manage permission
+    }  else {
+        error!("command_process_view Feed_id {} - user not authorized", feed_id);
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Unauthorized"))?));
+    }
+
+    // Only one processing at a time, a stuck processing can be replaced after the timeout.
+    if feed_view.processing_status == Some(FeedViewProcessingStatus::Processing) {
+        let timeout = Utc::now() - chrono::Duration::seconds(FEED_VIEW_PROCESSING_TIMEOUT_SECS);
+        let stuck = match feed_view.processing_start_date {
+            Some(start_date) => start_date <= timeout,
+            None => true
+        };
+        if !stuck {
+            return Ok(Some(middleware.reponse_err(Some(409), None, Some("View processing already in progress"))?));
+        }
+        warn!("command_process_view Processing of view {} timed out, starting a new processing", feed_view.feed_view_id);
+    }
+
+    // User is authorized. Start the process.
+    // The view is rebuilt in a new generation, readers keep the current generation until the processing is done.
+    // The data of a previous rebuild is removed by the maintenance (purge_generations).
+    let generation = feed_view.last_generation.max(feed_view.pending_generation).max(feed_view.generation).unwrap_or(0) + 1;
+
+    // Full processing covers the data already received, incremental processing resumes from there.
+    let watermark = get_feed_latest_save_date(middleware, feed_id.as_str()).await?;
+    let mut set_ops = doc!{
+        "watermark": watermark, "pending_generation": generation, "last_generation": generation,
+        "processing_status": "processing", "processed_items": 0,
+    };
+    if feed_view.pending_generation.is_some() {
+        set_ops.insert("purge_generations", true);
+    }
+    let ops = doc!{
+        "$set": set_ops,
+        "$unset": {
+            "rebuild_pending": true, "pending_since": true, "pending_last": true, "processing_since": true, "processing_until": true, "processing_end_date": true,
+            "total_items": true, "current_batch": true, "processing_error": true,
+        },
+        "$currentDate": {"modification_date": true, "processing_start_date": true},
+    };
+    collection_feed_view.update_one_with_session(filtre_view, ops, None, session).await?;
+
+    // Emit command to request start of processing of this feed view.
+    let process_event = ProcessStartEvent {
+        feed_id: feed_view.feed_id.to_owned(),
+        feed_view_id: feed_view.feed_view_id.to_owned(),
+        generation,
+    };
+    let routage = RoutageMessageAction::builder(DOMAIN_DATASOURCEMAPPER, "processFeedView", vec![Securite::L3Protege])
+        .timeout_blocking(5_000)
+        .build();
+    match middleware.transmettre_commande(routage, process_event).await? {
+        Some(message) => {
+            match parse_confirmation_response(&message) {
+                Some(confirmation) => {
+                    if Some(true) != confirmation.ok {
+                        error!("command_process_view Error starting feed processing: {:?}", message);
+                        let err = match confirmation.err.as_ref() { Some(inner) => Some(inner.as_str()), None => None};
+                        return Ok(Some(middleware.reponse_err(confirmation.code, None, err)?));
+                    }
+                }
+                None => {
+                    error!("command_process_view Error starting feed processing: {:?}", message);
+                    return Ok(Some(middleware.reponse_err(Some(500), None, Some("Error in response"))?))
+                }
+            }
+        },
+        None => {
+            error!("command_process_view No response when starting process");
+            return Ok(Some(middleware.reponse_err(Some(500), None, Some("No response when starting process"))?));
+        }
+    }
+
+    Ok(Some(middleware.reponse_ok(None, None)?))
+}
+
+#[derive(Deserialize)]
+struct InsertFeedViewGroupedDatedRequest {
+    feed_view_id: String,
+    feed_id: String,
+    data: Vec<FeedViewGroupedDatedItem>,
+    /// Generation to write into, the active generation when None.
+    generation: Option<i64>,
+    truncate: Option<bool>,
+    deduplicate: Option<bool>,
+}
+
+async fn command_insert_feed_view_data<M>(middleware: &M, mut message: MessageValide, session: &mut ClientSession)
+                                          -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+    where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    // Access check
+    if !message.certificat.verifier_roles_string(vec!["datasource_mapper".to_string()])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid role"))?));
+    }
+    if !message.certificat.verifier_exchanges(vec![Securite::L3Protege])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid security level"))?));
+    }
+
+    let command: InsertFeedViewGroupedDatedRequest = {
+        let message_ref = message.message.parse()?;
+        message_ref.contenu()?.deserialize()?
+    };
+
+    // Check feed (must not be deleted)
+    let filtre_feed = doc!{"feed_id": &command.feed_id, "deleted": false};
+    let collection_feeds = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    if collection_feeds.find_one(filtre_feed, None).await?.is_none() {
+        error!("command_insert_feed_view_grouped_dated Unknown feed_id");
+        return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed_id"))?));
+    };
+
+    // Check feed view (must not be deleted)
+    let filtre_feed_view = doc!{"feed_view_id": &command.feed_view_id, "deleted": false};
+    let collection_feed_views = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+    let feed_view = match collection_feed_views.find_one(filtre_feed_view, None).await? {
+        Some(inner) => inner,
+        None => {
+            error!("command_insert_feed_view_grouped_dated Unknown feed_view_id");
+            return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed_view_id"))?));
+        }
+    };
+
+    let generation = match command.generation {
+        Some(generation) => {
+            if Some(generation) != feed_view.generation && Some(generation) != feed_view.pending_generation {
+                error!("command_insert_feed_view_grouped_dated Generation {} is not active or pending", generation);
+                return Ok(Some(middleware.reponse_err(Some(409), None, Some("Unknown generation"))?));
+            }
+            Some(generation)
+        },
+        None => feed_view.generation
+    };
+
+    if !feed_view.decrypted && command.data.iter().any(|item| item.decrypted_data.is_some()) {
+        error!("command_insert_feed_view_grouped_dated Plaintext data rejected, view {} is not decrypted", command.feed_view_id);
+        return Ok(Some(middleware.reponse_err(Some(400), None, Some("View is not decrypted"))?));
+    }
+
+    let data_type = feed_view.get_data_type()?;
+    match data_type {
+        ViewDataType::Keyed => {
+            if command.data.iter().any(|item| item.group_id.is_none() || item.pub_date.is_none()) {
+                error!("command_insert_feed_view_grouped_dated Keyed view items require a group_id and a pub_date");
+                return Ok(Some(middleware.reponse_err(Some(400), None, Some("Items require a group_id and a pub_date"))?));
+            }
+        },
+        ViewDataType::TimeSeries => {
+            if command.data.iter().any(|item| item.value.is_none() || item.pub_date.is_none()) {
+                error!("command_insert_feed_view_grouped_dated TimeSeries view items require a value and a pub_date");
+                return Ok(Some(middleware.reponse_err(Some(400), None, Some("Items require a value and a pub_date"))?));
+            }
+        },
+        ViewDataType::Dated | ViewDataType::GroupedDated => ()
+    }
+
+    let data_collection_name = data_type.get_collection_name();
+    if Some(true) == command.truncate {
+        // Only truncates the generation being written
+        let collection_feed_view_data = middleware.get_collection(data_collection_name)?;
+        let delete_filtre = doc!{"feed_id": &command.feed_id, "feed_view_id": &command.feed_view_id, "generation": generation};
+        collection_feed_view_data.delete_many(delete_filtre, None).await?;
+    }
+
+    match data_type {
+        ViewDataType::Keyed => insert_view_data_keyed(middleware, data_collection_name, command.data, generation).await?,
+        ViewDataType::TimeSeries => insert_view_data_time_series(middleware, data_collection_name, command.data, generation).await?,
+        ViewDataType::Dated | ViewDataType::GroupedDated =>
+            insert_view_data_dated(middleware, data_collection_name, command.data, generation, command.deduplicate).await?,
+    }
+
+    Ok(Some(middleware.reponse_ok(None, None)?))
+}
+
+async fn insert_view_data_dated<M>(middleware: &M, data_collection_name: &str, data: Vec<FeedViewGroupedDatedItem>, generation: Option<i64>, deduplicate: Option<bool>)
+    -> Result<(), CommonError>
+    where M: MongoDao
+{
+    let collection_feed_view_data =
+        middleware.get_collection_typed::<FeedViewGroupedDatedRow>(data_collection_name)?;
+
+    // Convert all items into FeedViewDataRow type
+    let mut batch: Vec<FeedViewGroupedDatedRow> = Vec::with_capacity(data.len());
+    for item in data {
+        let mut row: FeedViewGroupedDatedRow = item.into();
+        row.generation = generation;
+        batch.push(row);
+    }
+    
+    if let Some(true) = deduplicate {
+        for item in batch {
+            let filtre = doc! {"data_id": &item.data_id, "feed_view_id": &item.feed_view_id, "generation": item.generation};
+            let item = convertir_to_bson(item)?;
+            let ops = doc! {"$setOnInsert": item};
+            let options = UpdateOptions::builder().upsert(true).build();
+            collection_feed_view_data.update_one(filtre, ops, options).await?;
+        }
+    } else {
+        if let Err(e) = collection_feed_view_data.insert_many(&batch, None).await {
+            if verifier_erreur_duplication_mongo(&e.kind) {
+                // Duplicate found. Insert missing items.
+                for item in batch {
+                    let filtre = doc!{"data_id": &item.data_id, "feed_view_id": &item.feed_view_id, "generation": item.generation};
+                    let item = convertir_to_bson(item)?;
+                    let ops = doc!{"$setOnInsert": item};
+                    let options = UpdateOptions::builder().upsert(true).build();
+                    collection_feed_view_data.update_one(filtre, ops, options).await?;
+                }
+            } else {
+                Err(e)?  // Re-throw
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Keeps only the most recent item of each group_id.
+async fn insert_view_data_keyed<M>(middleware: &M, data_collection_name: &str, data: Vec<FeedViewGroupedDatedItem>, generation: Option<i64>)
+    -> Result<(), CommonError>
+    where M: MongoDao
+{
+    let collection_feed_view_data =
+        middleware.get_collection_typed::<FeedViewGroupedDatedRow>(data_collection_name)?;
+
+    for item in data {
+        let mut row: FeedViewGroupedDatedRow = item.into();
+        row.generation = generation;
+
+        // Replace the current value of the group when it is not more recent.
+        let filtre = doc! {
+            "feed_view_id": &row.feed_view_id,
+            "generation": row.generation,
+            "group_id": &row.group_id,
+            "pub_date": {"$lte": row.pub_date},
+        };
+        let ops = doc! {"$set": convertir_to_bson(row)?};
+        let options = UpdateOptions::builder().upsert(true).build();
+        if let Err(e) = collection_feed_view_data.update_one(filtre, ops, options).await {
+            if verifier_erreur_duplication_mongo(&e.kind) {
+                // The group already has a more recent value
+                continue
+            }
+            Err(e)?  // Re-throw
+        }
+    }
+
+    Ok(())
+}
+
+async fn insert_view_data_time_series<M>(middleware: &M, data_collection_name: &str, data: Vec<FeedViewGroupedDatedItem>, generation: Option<i64>)
+    -> Result<(), CommonError>
+    where M: MongoDao
+{
+    let collection_feed_view_data =
+        middleware.get_collection_typed::<FeedViewTimeSeriesRow>(data_collection_name)?;
+
+    let mut batch: Vec<FeedViewTimeSeriesRow> = Vec::with_capacity(data.len());
+    for item in data {
+        let mut row = FeedViewTimeSeriesRow::try_from(item)?;
+        row.generation = generation;
+        batch.push(row);
+    }
+
+    if let Err(e) = collection_feed_view_data.insert_many(&batch, None).await {
+        if verifier_erreur_duplication_mongo(&e.kind) {
+            // Duplicate found. Insert missing items.
+            for item in batch {
+                let filtre = doc!{"data_id": &item.data_id, "feed_view_id": &item.feed_view_id, "generation": item.generation};
+                let item = convertir_to_bson(item)?;
+                let ops = doc!{"$setOnInsert": item};
+                let options = UpdateOptions::builder().upsert(true).build();
+                collection_feed_view_data.update_one(filtre, ops, options).await?;
+            }
+        } else {
+            Err(e)?  // Re-throw
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct ProcessViewProgressCommand {
+    feed_view_id: String,
+    processed_items: u64,
+    total_items: Option<u64>,
+    current_batch: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct ProcessViewDoneCommand {
+    feed_view_id: String,
+    processed_items: Option<u64>,
+    /// Generation of the processing request, the rebuilt generation is swapped in when it is the pending_generation.
+    generation: Option<i64>,
+    /// since of the incremental processing request.
+    #[serde(default, with="optionepochmilliseconds")]
+    since: Option<DateTime<Utc>>,
+    /// until of the incremental processing request.
+    #[serde(default, with="optionepochmilliseconds")]
+    until: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+struct ProcessViewFailedCommand {
+    feed_view_id: String,
+    error: String,
+    /// Generation of the processing request, the rebuilt generation is discarded when it is the pending_generation.
+    generation: Option<i64>,
+    /// since of the incremental processing request.
+    #[serde(default, with="optionepochmilliseconds")]
+    since: Option<DateTime<Utc>>,
+    /// until of the incremental processing request.
+    #[serde(default, with="optionepochmilliseconds")]
+    until: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct ViewProcessingEvent {
+    feed_id: String,
+    feed_view_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+async fn command_process_view_progress<M>(middleware: &M, message: MessageValide)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    // Access check
+    if !message.certificat.verifier_roles_string(vec!["datasource_mapper".to_string()])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid role"))?));
+    }
+    if !message.certificat.verifier_exchanges(vec![Securite::L3Protege])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid security level"))?));
+    }
+
+    let command: ProcessViewProgressCommand = {
+        let message_ref = message.message.parse()?;
+        message_ref.contenu()?.deserialize()?
+    };
+
+    // Progress is only recorded while the view is processing
+    let filtre = doc!{"feed_view_id": &command.feed_view_id, "deleted": false, "processing_status": "processing"};
+    let ops = doc!{"$set": {
+        "processed_items": command.processed_items as i64,
+        "total_items": command.total_items.map(|v| v as i64),
+        "current_batch": command.current_batch,
+    }};
+    let collection = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+    let result = collection.update_one(filtre, ops, None).await?;
+    if result.matched_count != 1 {
+        return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed_view_id or not processing"))?));
+    }
+
+    Ok(Some(middleware.reponse_ok(None, None)?))
+}
+
+async fn command_process_view_done<M>(middleware: &M, message: MessageValide, session: &mut ClientSession)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    // Access check
+    if !message.certificat.verifier_roles_string(vec!["datasource_mapper".to_string()])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid role"))?));
+    }
+    if !message.certificat.verifier_exchanges(vec![Securite::L3Protege])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid security level"))?));
+    }
 
-#[derive(Serialize)]
-struct ProcessStartEvent {
-    feed_id: String,
-    feed_view_id: String,
+    let command: ProcessViewDoneCommand = {
+        let message_ref = message.message.parse()?;
+        message_ref.contenu()?.deserialize()?
+    };
+
+    let filtre = doc!{"feed_view_id": &command.feed_view_id, "deleted": false};
+    let collection = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+    let feed_view = match collection.find_one(filtre, None).await? {
+        Some(inner) => inner,
+        None => return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed_view_id"))?))
+    };
+
+    let is_rebuild = command.generation.is_some() && command.generation == feed_view.pending_generation;
+
+    let mut set_ops = doc!{"ready": true, "processing_status": "done"};
+    if let Some(processed_items) = command.processed_items {
+        set_ops.insert("processed_items", processed_items as i64);
+    }
+    let mut unset_ops = doc!{"processing_since": true, "processing_until": true, "current_batch": true, "processing_error": true};
+    let mut filtre = doc!{"feed_view_id": &command.feed_view_id, "processing_status": "processing"};
+    if is_rebuild {
+        // Swap in the rebuilt generation, the previous generation is removed by the maintenance.
+        set_ops.insert("generation", command.generation);
+        set_ops.insert("purge_generations", true);
+        unset_ops.insert("pending_generation", true);
+        filtre.insert("pending_generation", command.generation);
+    } else {
+        // Incremental processing, only the current request can complete it
+        filtre.insert("pending_generation", None::<i64>);
+        filtre.insert("generation", command.generation);
+        filtre.insert("processing_since", command.since);
+        filtre.insert("processing_until", command.until);
+    }
+    let ops = doc!{
+        "$set": set_ops,
+        "$unset": unset_ops,
+        "$currentDate": {"modification_date": true, "processing_end_date": true},
+    };
+    let result = collection.update_one_with_session(filtre, ops, None, session).await?;
+    if result.matched_count != 1 {
+        warn!("command_process_view_done Stale or unknown processing of view {}, ignored", command.feed_view_id);
+        return Ok(Some(middleware.reponse_err(Some(409), None, Some("Stale or unknown processing request"))?));
+    }
+
+    let event = ViewProcessingEvent { feed_id: feed_view.feed_id, feed_view_id: feed_view.feed_view_id, error: None };
+    let routage = RoutageMessageAction::builder(DOMAIN_NAME, EVENT_VIEW_PROCESSING_DONE, vec![Securite::L2Prive]).build();
+    middleware.emettre_evenement(routage, event).await?;
+
+    Ok(Some(middleware.reponse_ok(None, None)?))
 }
 
-async fn command_process_view<M>(middleware: &M, mut message: MessageValide, session: &mut ClientSession)
+async fn command_process_view_failed<M>(middleware: &M, message: MessageValide, session: &mut ClientSession)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    // Access check
+    if !message.certificat.verifier_roles_string(vec!["datasource_mapper".to_string()])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid role"))?));
+    }
+    if !message.certificat.verifier_exchanges(vec![Securite::L3Protege])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid security level"))?));
+    }
+
+    let command: ProcessViewFailedCommand = {
+        let message_ref = message.message.parse()?;
+        message_ref.contenu()?.deserialize()?
+    };
+
+    let filtre = doc!{"feed_view_id": &command.feed_view_id, "deleted": false};
+    let collection = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+    let feed_view = match collection.find_one(filtre, None).await? {
+        Some(inner) => inner,
+        None => return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed_view_id"))?))
+    };
+    warn!("command_process_view_failed Processing of view {} failed: {}", command.feed_view_id, command.error);
+
+    let is_rebuild = command.generation.is_some() && command.generation == feed_view.pending_generation;
+
+    let mut set_ops = doc!{"processing_status": "failed", "processing_error": &command.error};
+    let mut filtre = doc!{"feed_view_id": &command.feed_view_id, "processing_status": "processing"};
+    let ops = if is_rebuild {
+        // Discard the partial rebuild, readers keep the current generation.
+        // The data of the rebuild is removed by the maintenance.
+        set_ops.insert("purge_generations", true);
+        filtre.insert("pending_generation", command.generation);
+        doc!{
+            "$set": set_ops,
+            "$unset": {"pending_generation": true, "processing_since": true, "processing_until": true},
+            "$currentDate": {"modification_date": true, "processing_end_date": true},
+        }
+    } else {
+        // Incremental processing, only the current request can fail it.
+        // Restore the watermark, the data items of the failed request get requested again with the next new data.
+        set_ops.insert("watermark", feed_view.processing_since);
+        filtre.insert("pending_generation", None::<i64>);
+        filtre.insert("generation", command.generation);
+        filtre.insert("processing_since", command.since);
+        filtre.insert("processing_until", command.until);
+        doc!{
+            "$set": set_ops,
+            "$unset": {"processing_since": true, "processing_until": true},
+            "$currentDate": {"modification_date": true, "processing_end_date": true},
+        }
+    };
+    let result = collection.update_one_with_session(filtre, ops, None, session).await?;
+    if result.matched_count != 1 {
+        warn!("command_process_view_failed Stale or unknown processing of view {}, ignored", command.feed_view_id);
+        return Ok(Some(middleware.reponse_err(Some(409), None, Some("Stale or unknown processing request"))?));
+    }
+
+    let event = ViewProcessingEvent { feed_id: feed_view.feed_id, feed_view_id: feed_view.feed_view_id, error: Some(command.error) };
+    let routage = RoutageMessageAction::builder(DOMAIN_NAME, EVENT_VIEW_PROCESSING_FAILED, vec![Securite::L2Prive]).build();
+    middleware.emettre_evenement(routage, event).await?;
+
+    Ok(Some(middleware.reponse_ok(None, None)?))
+}
+
+async fn command_grant_feed_access<M>(middleware: &M, mut message: MessageValide, manager: &DataCollectorDomainManager, session: &mut ClientSession)
     -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
 where M: GenerateurMessages + MongoDao + ValidateurX509
 {
@@ -567,35 +1829,28 @@ where M: GenerateurMessages + MongoDao + ValidateurX509
         Ok(inner) => match inner {
             Some(user) => user.to_owned(),
             None => {
-                error!("command_process_view Invalid certificate, no user_id - command rejected");
+                error!("command_grant_feed_access Invalid certificate, no user_id - command rejected");
                 return Ok(Some(middleware.reponse_err(Some(401), None, Some("Invalid certificate"))?));
             }
         },
-        Err(e) => Err(format!("command_update_feed_view Erreur get_user_id() : {:?}", e))?
+        Err(e) => Err(format!("command_grant_feed_access Erreur get_user_id() : {:?}", e))?
     };
     let is_admin = message.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
 
     // Deserialize to validate the format
-    let command: ProcessViewRequest = message_owned.deserialize()?;
+    let command: GrantFeedAccessTransaction = message_owned.deserialize()?;
 
-    // Check if the user is allowed to create a feed view on this feed
-    let filtre_view = doc!{"feed_view_id": &command.feed_view_id};
-    let collection_feed_view = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
-    let feed_view = match collection_feed_view.find_one(filtre_view.clone(), None).await? {
-        Some(inner) => inner,
-        None => {
-            error!("command_process_view No feed view with the provided Id");
-            return Ok(Some(middleware.reponse_err(Some(404), None, Some("No such feed view"))?));
-        }
-    };
+    if command.user_id == user_id {
+        return Ok(Some(middleware.reponse_err(Some(400), None, Some("Cannot share a feed with its owner"))?));
+    }
 
-    let feed_id = &feed_view.feed_id;
-    let filtre_feed = doc!{"feed_id": feed_id};
-    let collection_feed = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
-    let feed = match collection_feed.find_one(filtre_feed, None).await? {
+    // Only the owner is allowed to share the feed
+    let filtre = doc!{"feed_id": &command.feed_id, "deleted": false};
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let feed = match collection.find_one(filtre, None).await? {
         Some(feed) => feed,
         None => {
-            error!("command_process_view Unknown feed_id {} - command rejected", feed_id);
+            error!("command_grant_feed_access Unknown feed_id {} - command rejected", command.feed_id);
             return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed"))?));
         }
     };
@@ -605,142 +1860,172 @@ where M: GenerateurMessages + MongoDao + ValidateurX509
     } else if is_admin && feed.user_id.is_none() {
         // Ok, system feed managed by admin
     }  else {
-        error!("command_process_view Feed_id {} - user not authorized", feed_id);
+        error!("command_grant_feed_access Sharing feed_id {} - user not authorized", command.feed_id);
         return Ok(Some(middleware.reponse_err(Some(401), None, Some("Unauthorized"))?));
     }
 
-    // User is authorized. Start the process.
-    let ops = doc!{
-        "$set": {"ready": false},
-        "$currentDate": {"modification_date": true, "processing_start_date": true},
-    };
-    collection_feed_view.update_one_with_session(filtre_view, ops, None, session).await?;
+    // Save and run new transaction
+    sauvegarder_traiter_transaction_v2(middleware, message, manager, session).await?;
 
-    // Emit command to request start of processing of this feed view.
-    let process_event = ProcessStartEvent {
-        feed_id: feed_view.feed_id.to_owned(),
-        feed_view_id: feed_view.feed_view_id.to_owned()
-    };
-    let routage = RoutageMessageAction::builder(DOMAIN_DATASOURCEMAPPER, "processFeedView", vec![Securite::L3Protege])
-        .timeout_blocking(5_000)
-        .build();
-    match middleware.transmettre_commande(routage, process_event).await? {
-        Some(message) => {
-            match parse_confirmation_response(&message) {
-                Some(confirmation) => {
-                    if Some(true) != confirmation.ok {
-                        error!("command_process_view Error starting feed processing: {:?}", message);
-                        let err = match confirmation.err.as_ref() { Some(inner) => Some(inner.as_str()), None => None};
-                        return Ok(Some(middleware.reponse_err(confirmation.code, None, err)?));
-                    }
-                }
-                None => {
-                    error!("command_process_view Error starting feed processing: {:?}", message);
-                    return Ok(Some(middleware.reponse_err(Some(500), None, Some("Error in response"))?))
-                }
+    Ok(Some(middleware.reponse_ok(None, None)?))
+}
+
+async fn command_revoke_feed_access<M>(middleware: &M, mut message: MessageValide, manager: &DataCollectorDomainManager, session: &mut ClientSession)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    let mut message_owned = message.message.parse_to_owned()?;
+
+    let user_id = match message.certificat.get_user_id() {
+        Ok(inner) => match inner {
+            Some(user) => user.to_owned(),
+            None => {
+                error!("command_revoke_feed_access Invalid certificate, no user_id - command rejected");
+                return Ok(Some(middleware.reponse_err(Some(401), None, Some("Invalid certificate"))?));
             }
         },
+        Err(e) => Err(format!("command_revoke_feed_access Erreur get_user_id() : {:?}", e))?
+    };
+    let is_admin = message.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
+
+    // Deserialize to validate the format
+    let command: RevokeFeedAccessTransaction = message_owned.deserialize()?;
+
+    let filtre = doc!{"feed_id": &command.feed_id};
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let feed = match collection.find_one(filtre, None).await? {
+        Some(feed) => feed,
         None => {
-            error!("command_process_view No response when starting process");
-            return Ok(Some(middleware.reponse_err(Some(500), None, Some("No response when starting process"))?));
+            error!("command_revoke_feed_access Unknown feed_id {} - command rejected", command.feed_id);
+            return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed"))?));
         }
+    };
+
+    if feed.user_id == Some(user_id) {
+        // Ok, feed belongs to user
+    } else if is_admin && feed.user_id.is_none() {
+        // Ok, system feed managed by admin
+    }  else {
+        error!("command_revoke_feed_access Revoking access on feed_id {} - user not authorized", command.feed_id);
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Unauthorized"))?));
     }
 
-    Ok(Some(middleware.reponse_ok(None, None)?))
-}
+    // Save and run new transaction
+    sauvegarder_traiter_transaction_v2(middleware, message, manager, session).await?;
 
-#[derive(Deserialize)]
-struct InsertFeedViewGroupedDatedRequest {
-    feed_view_id: String,
-    feed_id: String,
-    data: Vec<FeedViewGroupedDatedItem>,
-    truncate: Option<bool>,
-    deduplicate: Option<bool>,
+    Ok(Some(middleware.reponse_ok(None, None)?))
 }
 
-async fn command_insert_feed_view_data<M>(middleware: &M, mut message: MessageValide, session: &mut ClientSession)
-                                          -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
-    where M: GenerateurMessages + MongoDao + ValidateurX509
+async fn command_transfer_feed<M>(middleware: &M, mut message: MessageValide, manager: &DataCollectorDomainManager, session: &mut ClientSession)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
 {
-    // Access check
-    if !message.certificat.verifier_roles_string(vec!["datasource_mapper".to_string()])? {
-        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid role"))?));
-    }
-    if !message.certificat.verifier_exchanges(vec![Securite::L3Protege])? {
-        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid security level"))?));
-    }
+    let mut message_owned = message.message.parse_to_owned()?;
 
-    let command: InsertFeedViewGroupedDatedRequest = {
-        let message_ref = message.message.parse()?;
-        message_ref.contenu()?.deserialize()?
+    let user_id = match message.certificat.get_user_id() {
+        Ok(inner) => match inner {
+            Some(user) => user.to_owned(),
+            None => {
+                error!("command_transfer_feed Invalid certificate, no user_id - command rejected");
+                return Ok(Some(middleware.reponse_err(Some(401), None, Some("Invalid certificate"))?));
+            }
+        },
+        Err(e) => Err(format!("command_transfer_feed Erreur get_user_id() : {:?}", e))?
     };
+    let is_admin = message.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
 
-    // Check feed (must not be deleted)
-    let filtre_feed = doc!{"feed_id": &command.feed_id, "deleted": false};
-    let collection_feeds = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
-    if collection_feeds.find_one(filtre_feed, None).await?.is_none() {
-        error!("command_insert_feed_view_grouped_dated Unknown feed_id");
-        return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed_id"))?));
-    };
+    // Deserialize to validate the format
+    let command: TransferFeedTransaction = message_owned.deserialize()?;
 
-    // Check feed view (must not be deleted)
-    let filtre_feed_view = doc!{"feed_view_id": &command.feed_view_id, "deleted": false};
-    let collection_feed_views = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
-    let feed_view = match collection_feed_views.find_one(filtre_feed_view, None).await? {
-        Some(inner) => inner,
+    let filtre = doc!{"feed_id": &command.feed_id, "deleted": false};
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let feed = match collection.find_one(filtre, None).await? {
+        Some(feed) => feed,
         None => {
-            error!("command_insert_feed_view_grouped_dated Unknown feed_view_id");
-            return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed_view_id"))?));
+            error!("command_transfer_feed Unknown feed_id {} - command rejected", command.feed_id);
+            return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed"))?));
         }
     };
 
-    let data_type = match feed_view.data_type.as_ref() {
-        Some(data_type) => ViewDataType::try_from(data_type.as_str())?,
-        None => ViewDataType::GroupedDated,  // Default to grouped-dated
-    };
-    let data_collection_name = match data_type {
-        ViewDataType::Dated => COLLECTION_NAME_FEED_VIEW_DATED,
-        ViewDataType::GroupedDated => COLLECTION_NAME_FEED_VIEW_GROUPED_DATED,
-    };
-    
-    let collection_feed_view_data =
-        middleware.get_collection_typed::<FeedViewGroupedDatedRow>(data_collection_name)?;
-    if Some(true) == command.truncate {
-        let delete_filtre = doc!{"feed_id": &command.feed_id, "feed_view_id": &command.feed_view_id};
-        collection_feed_view_data.delete_many(delete_filtre, None).await?;
+    if feed.user_id == command.user_id {
+        return Ok(Some(middleware.reponse_err(Some(400), None, Some("Feed already belongs to this owner"))?));
     }
 
-    // Convert all items into FeedViewDataRow type
-    let mut batch: Vec<FeedViewGroupedDatedRow> = Vec::with_capacity(command.data.len());
-    for item in command.data {
-        batch.push(item.into());
-    }
-    
-    if let Some(true) = command.deduplicate {
-        for item in batch {
-            let filtre = doc! {"data_id": &item.data_id, "feed_view_id": &item.feed_view_id};
-            let item = convertir_to_bson(item)?;
-            let ops = doc! {"$setOnInsert": item};
-            let options = UpdateOptions::builder().upsert(true).build();
-            collection_feed_view_data.update_one(filtre, ops, options).await?;
-        }
+    // Source side: the feed must belong to the user, or the user is an admin.
+    if is_admin {
+        // Ok, admin can take any feed
+    } else if feed.user_id.as_deref() == Some(user_id.as_str()) {
+        // Ok, feed belongs to user
     } else {
-        if let Err(e) = collection_feed_view_data.insert_many(&batch, None).await {
-            if verifier_erreur_duplication_mongo(&e.kind) {
-                // Duplicate found. Insert missing items.
-                for item in batch {
-                    let filtre = doc!{"data_id": &item.data_id, "feed_view_id": &item.feed_view_id};
-                    let item = convertir_to_bson(item)?;
-                    let ops = doc!{"$setOnInsert": item};
-                    let options = UpdateOptions::builder().upsert(true).build();
-                    collection_feed_view_data.update_one(filtre, ops, options).await?;
-                }
-            } else {
-                Err(e)?  // Re-throw
+        error!("command_transfer_feed Transferring feed_id {} - user not authorized", command.feed_id);
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Unauthorized"))?));
+    }
+
+    // Destination side: only an admin can promote a feed to a system feed.
+    // A transfer to a user is only applied once the new owner accepts it (acceptFeedTransfer).
+    let new_user_id = match command.user_id {
+        Some(inner) => inner,
+        None => {
+            if !is_admin {
+                error!("command_transfer_feed Promoting feed_id {} to system feed - user not authorized", command.feed_id);
+                return Ok(Some(middleware.reponse_err(Some(401), None, Some("Unauthorized"))?));
             }
+            // Save and run new transaction
+            sauvegarder_traiter_transaction_v2(middleware, message, manager, session).await?;
+            return Ok(Some(middleware.reponse_ok(None, None)?));
         }
+    };
+
+    // Save and run new transaction
+    sauvegarder_traiter_transaction_v2(middleware, message, manager, session).await?;
+
+    // Let the new owner know a feed is waiting for acceptance
+    let event = FeedTransferRequestedEvent { feed_id: command.feed_id, user_id: new_user_id, from_user_id: user_id };
+    let routage = RoutageMessageAction::builder(DOMAIN_NAME, EVENT_FEED_TRANSFER_REQUESTED, vec![Securite::L2Prive]).build();
+    middleware.emettre_evenement(routage, event).await?;
+
+    Ok(Some(middleware.reponse_ok(None, None)?))
+}
+
+#[derive(Serialize)]
+struct FeedTransferRequestedEvent {
+    feed_id: String,
+    /// Recipient of the transfer.
+    user_id: String,
+    from_user_id: String,
+}
+
+async fn command_accept_feed_transfer<M>(middleware: &M, mut message: MessageValide, manager: &DataCollectorDomainManager, session: &mut ClientSession)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    let message_owned = message.message.parse_to_owned()?;
+
+    let user_id = match message.certificat.get_user_id() {
+        Ok(inner) => match inner {
+            Some(user) => user.to_owned(),
+            None => {
+                error!("command_accept_feed_transfer Invalid certificate, no user_id - command rejected");
+                return Ok(Some(middleware.reponse_err(Some(401), None, Some("Invalid certificate"))?));
+            }
+        },
+        Err(e) => Err(format!("command_accept_feed_transfer Erreur get_user_id() : {:?}", e))?
+    };
+
+    // Deserialize to validate the format
+    let command: AcceptFeedTransferTransaction = message_owned.deserialize()?;
+
+    // Only the user the feed was offered to can accept it
+    let filtre = doc!{"feed_id": &command.feed_id, "deleted": false, "transfer_user_id": &user_id};
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    if collection.find_one(filtre, None).await?.is_none() {
+        error!("command_accept_feed_transfer No transfer of feed_id {} to this user - command rejected", command.feed_id);
+        return Ok(Some(middleware.reponse_err(Some(404), None, Some("No pending transfer"))?));
     }
 
+    // Save and run new transaction
+    sauvegarder_traiter_transaction_v2(middleware, message, manager, session).await?;
+
     Ok(Some(middleware.reponse_ok(None, None)?))
 }
 
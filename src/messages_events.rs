@@ -1,17 +1,83 @@
-use log::debug;
-use millegrilles_common_rust::certificats::ValidateurX509;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use millegrilles_common_rust::certificats::{ValidateurX509, VerificateurPermissions};
+use millegrilles_common_rust::constantes::Securite;
 use millegrilles_common_rust::generateur_messages::GenerateurMessages;
+use millegrilles_common_rust::middleware::sauvegarder_traiter_transaction_serializable_v2;
 use millegrilles_common_rust::millegrilles_cryptographie::messages_structs::MessageMilleGrillesBufferDefault;
-use millegrilles_common_rust::mongo_dao::MongoDao;
+use millegrilles_common_rust::mongo_dao::{start_transaction_regular, MongoDao};
+use millegrilles_common_rust::rabbitmq_dao::TypeMessageOut;
 use millegrilles_common_rust::recepteur_messages::MessageValide;
 use millegrilles_common_rust::error::Error as CommonError;
 
+use crate::constants::*;
 use crate::domain_manager::DataCollectorDomainManager;
+use crate::transactions_struct::PurgeUserFeedsTransaction;
 
 pub async fn consume_event<M>(middleware: &M, message: MessageValide, manager: &DataCollectorDomainManager)
     -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
     where M: ValidateurX509 + GenerateurMessages + MongoDao
 {
     debug!("Consume event: {:?}", &message.type_message);
-    todo!()
+
+    let (domain, action) = match &message.type_message {
+        TypeMessageOut::Evenement(r) => (r.domaine.clone(), r.action.clone()),
+        _ => Err(CommonError::Str("events Bad message type, must be event"))?
+    };
+
+    match (domain.as_str(), action.as_str()) {
+        (DOMAIN_USER_ACCOUNTS, EVENT_USER_ACCOUNT_DELETED) => event_user_account_deleted(middleware, message, manager).await,
+        // Unknown event
+        _ => {
+            warn!("consume_event Unsupported event {}/{}, ignored", domain, action);
+            Ok(None)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct UserAccountDeletedEvent {
+    #[serde(alias = "userId")]
+    user_id: String,
+}
+
+/// Purges the feeds of a deleted user account.
+/// Source: the CoreMaitreDesComptes (user accounts) domain of the millegrilles core emits
+/// evenement.CoreMaitreDesComptes.supprimerUsager on 3.protege with the user_id once the account is deleted.
+async fn event_user_account_deleted<M>(middleware: &M, message: MessageValide, manager: &DataCollectorDomainManager)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+    where M: ValidateurX509 + GenerateurMessages + MongoDao
+{
+    if ! message.certificat.verifier_domaines(vec![DOMAIN_USER_ACCOUNTS.to_string()])? {
+        warn!("event_user_account_deleted Event not emitted by the {} domain, ignored", DOMAIN_USER_ACCOUNTS);
+        return Ok(None);
+    } else if ! message.certificat.verifier_exchanges(vec![Securite::L3Protege, Securite::L4Secure])? {
+        warn!("event_user_account_deleted Invalid security level, ignored");
+        return Ok(None);
+    }
+
+    let event: UserAccountDeletedEvent = {
+        let message_ref = message.message.parse()?;
+        message_ref.contenu()?.deserialize()?
+    };
+
+    info!("event_user_account_deleted Purging feeds of user {}", event.user_id);
+    let transaction = PurgeUserFeedsTransaction { user_id: event.user_id };
+
+    let mut session = middleware.get_session().await?;
+    start_transaction_regular(&mut session).await?;
+    match sauvegarder_traiter_transaction_serializable_v2(
+        middleware, &transaction, manager, &mut session, DOMAIN_NAME, TRANSACTION_PURGE_USER_FEEDS).await
+    {
+        Ok(_) => session.commit_transaction().await?,
+        Err(e) => {
+            warn!("event_user_account_deleted Error purging feeds of user {}: {:?}", transaction.user_id, e);
+            session.abort_transaction().await?;
+            // Not acknowledged, the event is delivered again to retry the purge.
+            Err(e)?
+        }
+    }
+
+    Ok(None)
 }
@@ -1,29 +1,29 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use log::{debug, error, warn};
 use serde::{Deserialize, Serialize};
 
 use millegrilles_common_rust::bson::{doc, Document};
 use millegrilles_common_rust::certificats::{ValidateurX509, VerificateurPermissions};
-use millegrilles_common_rust::chrono::{DateTime, Utc};
+use millegrilles_common_rust::chrono::{DateTime, TimeZone, Utc};
 use millegrilles_common_rust::common_messages::ResponseRequestDechiffrageV2Cle;
 use millegrilles_common_rust::constantes::{RolesCertificats, Securite, DELEGATION_GLOBALE_PROPRIETAIRE, SECURITE_1_PUBLIC, SECURITE_2_PRIVE, SECURITE_3_PROTEGE};
 use millegrilles_common_rust::generateur_messages::GenerateurMessages;
 use millegrilles_common_rust::millegrilles_cryptographie::messages_structs::{MessageMilleGrillesBufferDefault, MessageMilleGrillesOwned};
-use millegrilles_common_rust::mongo_dao::{start_transaction_regular, MongoDao};
+use millegrilles_common_rust::mongo_dao::{convertir_bson_deserializable, opt_chrono_datetime_as_bson_datetime, start_transaction_regular, MongoDao};
 use millegrilles_common_rust::recepteur_messages::MessageValide;
 use millegrilles_common_rust::error::Error as CommonError;
 use millegrilles_common_rust::millegrilles_cryptographie::chiffrage_docs::EncryptedDocument;
 use millegrilles_common_rust::mongodb::ClientSession;
-use millegrilles_common_rust::mongodb::options::{CountOptions, FindOptions, Hint};
+use millegrilles_common_rust::mongodb::options::{AggregateOptions, CountOptions, FindOptions, Hint};
 use millegrilles_common_rust::rabbitmq_dao::TypeMessageOut;
 use millegrilles_common_rust::millegrilles_cryptographie::messages_structs::{epochseconds, optionepochseconds, epochmilliseconds, optionepochmilliseconds};
 use millegrilles_common_rust::millegrilles_cryptographie::x509::EnveloppeCertificat;
 use crate::constants::*;
-use crate::data_mongodb::{DataCollectorFilesRow, DataCollectorRow, DataCollectorRowIds, DataFeedRow, FeedViewGroupedDatedRow, FeedViewRow};
+use crate::data_mongodb::{DataCollectorFilesRow, DataCollectorRow, DataCollectorRowIds, DataFeedRow, FeedStatusReport, FeedStatusRow, FeedViewGroupedDatedRow, FeedViewProcessingStatus, FeedViewRow, FeedViewTimeSeriesRow};
 use crate::domain_manager::DataCollectorDomainManager;
 use crate::keymaster::{fetch_decryption_keys, get_decrypted_keys, get_encrypted_keys};
 use crate::messages_commands::FuuidVolatile;
-use crate::transactions_struct::{CreateFeedTransaction, FeedViewGroupedDatedItem, FileItem};
+use crate::transactions_struct::{CreateFeedTransaction, FeedAclEntry, FeedRetentionPolicy, FeedViewGroupedDatedItem, FileItem};
 
 pub async fn consume_request<M>(middleware: &M, message: MessageValide, _manager: &DataCollectorDomainManager)
                                 -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
@@ -57,6 +57,7 @@ pub async fn consume_request<M>(middleware: &M, message: MessageValide, _manager
         // Commandes standard
         REQUEST_GET_FEEDS => request_get_feeds(middleware, message).await,
         REQUEST_GET_FEEDS_FOR_SCRAPER => request_get_feeds_for_scraper(middleware, message).await,
+        REQUEST_GET_DUE_FEEDS_FOR_SCRAPER => request_get_due_feeds_for_scraper(middleware, message).await,
         REQUEST_GET_FEED_VIEWS => request_get_feed_views(middleware, message).await,
         REQUEST_CHECK_EXISTING_DATA_IDS => request_check_existing_data_ids(middleware, message).await,
         REQUEST_GET_DATA_ITEMS_MOST_RECENT => request_get_data_items_most_recent(middleware, message).await,
@@ -64,6 +65,12 @@ pub async fn consume_request<M>(middleware: &M, message: MessageValide, _manager
         REQUEST_GET_FUUIDS_VOLATILE => request_get_fuuids_volatile(middleware, message).await,
         REQUEST_GET_FEED_DATA => request_feed_data(middleware, message).await,
         REQUEST_GET_VIEW_DATA => request_view_data(middleware, message).await,
+        REQUEST_GET_DATA_ITEMS_V1_TO_MIGRATE => request_get_data_items_v1_to_migrate(middleware, message).await,
+        REQUEST_GET_FEED_STATUS => request_get_feed_status(middleware, message).await,
+        REQUEST_GET_VIEW_AGGREGATES => request_view_aggregates(middleware, message).await,
+        REQUEST_GET_VIEW_GROUPS => request_view_groups(middleware, message).await,
+        REQUEST_GET_VIEW_GROUPS_LATEST => request_view_groups_latest(middleware, message).await,
+        REQUEST_GET_FEED_TRANSFERS => request_get_feed_transfers(middleware, message).await,
         // Unknown request
         _ => Ok(Some(middleware.reponse_err(Some(99), None, Some("Unknown request"))?))
     }
@@ -85,6 +92,51 @@ struct FeedResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user_id: Option<String>,
     pub deleted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention: Option<FeedRetentionPolicy>,
+    #[serde(default, with="optionepochseconds", skip_serializing_if = "Option::is_none")]
+    pub last_polled_at: Option<DateTime<Utc>>,
+    #[serde(default, with="optionepochseconds", skip_serializing_if = "Option::is_none")]
+    pub next_poll_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stale: Option<bool>,
+    /// Users with access to the feed. Only returned to the owner.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acl: Option<Vec<FeedAclEntry>>,
+    /// Recipient of a transfer waiting for acceptance. Only returned to the owner.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transfer_user_id: Option<String>,
+    /// Health of the feed from the scraper reports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<FeedStatusSummary>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FeedStatusSummary {
+    #[serde(default, with="optionepochseconds", skip_serializing_if = "Option::is_none")]
+    last_attempt: Option<DateTime<Utc>>,
+    #[serde(default, with="optionepochseconds", skip_serializing_if = "Option::is_none")]
+    last_success: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    http_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    item_count: Option<u64>,
+    consecutive_failures: u32,
+}
+
+impl From<FeedStatusRow> for FeedStatusSummary {
+    fn from(value: FeedStatusRow) -> Self {
+        Self {
+            last_attempt: value.last_attempt,
+            last_success: value.last_success,
+            last_error: value.last_error,
+            http_status: value.http_status,
+            item_count: value.item_count,
+            consecutive_failures: value.consecutive_failures,
+        }
+    }
 }
 
 impl From<DataFeedRow> for FeedResponse {
@@ -100,6 +152,13 @@ impl From<DataFeedRow> for FeedResponse {
             encrypted_feed_information: value.encrypted_feed_information,
             user_id: value.user_id,
             deleted: value.deleted,
+            retention: value.retention,
+            last_polled_at: value.last_polled_at,
+            next_poll_at: value.next_poll_at,
+            stale: value.stale,
+            acl: value.acl,
+            transfer_user_id: value.transfer_user_id,
+            status: None,
         }
     }
 }
@@ -111,7 +170,7 @@ struct RequestGetFeeds {
 }
 
 #[derive(Serialize)]
-struct RequestGetFeedsResponse {
+pub struct RequestGetFeedsResponse {
     ok: bool,
     feeds: Vec<FeedResponse>,
     keys: MessageMilleGrillesOwned,
@@ -145,13 +204,13 @@ async fn request_get_feeds<M>(middleware: &M, mut message: MessageValide)
         let mut filtre = if is_admin {
             doc! {"user_id": null, "deleted": deleted_flag}  // Only fetch system feeds
         } else {
-            // Regular private user, only load user feeds and private system feeds.
+            // Regular private user, only load user feeds, shared feeds and private system feeds.
             doc!(
                 "$or": [
                     {"user_id": &user_id},
+                    {"acl.user_id": &user_id},
                     {"user_id": null, "security_level": {"$in": [SECURITE_1_PUBLIC, SECURITE_2_PRIVE]}},
                 ],
-                "user_id": {"$in": [&user_id, null]},
                 "deleted": deleted_flag
             )
         };
@@ -184,9 +243,36 @@ where M: GenerateurMessages + MongoDao + ValidateurX509
     Ok(Some(middleware.build_reponse(response_message)?.0))
 }
 
-async fn get_feeds<M>(middleware: &M, message: &mut MessageValide, filtre: Document) -> Result<RequestGetFeedsResponse, CommonError>
+/// Returns the active feeds that are due for polling.
+async fn request_get_due_feeds_for_scraper<M>(middleware: &M, mut message: MessageValide)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    if ! message.certificat.verifier_roles_string(vec!["web_scraper".to_string()])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid role"))?));
+    } else if ! message.certificat.verifier_exchanges(vec![Securite::L1Public])? {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied - invalid security level"))?));
+    }
+
+    let filtre = doc! {
+        "deleted": false,
+        "active": true,
+        "$or": [
+            {"next_poll_at": null},
+            {"next_poll_at": {"$lte": Utc::now()}},
+        ]
+    };
+    let response_message = get_feeds(middleware, &mut message, filtre).await?;
+
+    Ok(Some(middleware.build_reponse(response_message)?.0))
+}
+
+pub async fn get_feeds<M>(middleware: &M, message: &mut MessageValide, filtre: Document) -> Result<RequestGetFeedsResponse, CommonError>
     where M: GenerateurMessages + MongoDao
 {
+    let user_id = message.certificat.get_user_id()?;
+    let is_admin = message.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
+
     let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
     let mut cursor = collection.find(filtre, None).await?;
     let mut key_ids = HashSet::new();
@@ -203,7 +289,33 @@ async fn get_feeds<M>(middleware: &M, message: &mut MessageValide, filtre: Docum
         if let Some(cle_id) = row.encrypted_feed_information.cle_id.clone() {
             key_ids.insert(cle_id);
         }
-        feeds.push(row.into());
+        let is_owner = match row.user_id.as_ref() {
+            Some(owner) => user_id.as_deref() == Some(owner.as_str()),
+            None => is_admin
+        };
+        let mut feed: FeedResponse = row.into();
+        if !is_owner {
+            feed.acl = None;  // Do not disclose the other grantees
+            feed.transfer_user_id = None;
+        }
+        feeds.push(feed);
+    }
+
+    // Add the status summary, the history is only returned by getFeedStatus.
+    let feed_ids: Vec<&str> = feeds.iter().map(|f| f.feed_id.as_str()).collect();
+    let filtre_status = doc!{"feed_id": {"$in": feed_ids}};
+    let options = FindOptions::builder().projection(doc!{"history": 0}).build();
+    let collection = middleware.get_collection_typed::<FeedStatusRow>(COLLECTION_NAME_FEED_STATUS)?;
+    let mut cursor = collection.find(filtre_status, options).await?;
+    let mut statuses = HashMap::new();
+    while cursor.advance().await? {
+        let row = cursor.deserialize_current()?;
+        statuses.insert(row.feed_id.clone(), row);
+    }
+    for feed in feeds.iter_mut() {
+        if let Some(status) = statuses.remove(&feed.feed_id) {
+            feed.status = Some(status.into());
+        }
     }
 
     // Recover all decryption keys, re-encrypt them for the client
@@ -219,6 +331,8 @@ async fn get_feeds<M>(middleware: &M, message: &mut MessageValide, filtre: Docum
 struct CheckExistingDataIdsRequest {
     feed_id: String,
     data_ids: Vec<String>,
+    /// Data generation to check: 1 for saveDataItem, 2 for saveDataItemV2. Checks both when None.
+    version: Option<u8>,
 }
 
 #[derive(Serialize)]
@@ -240,18 +354,30 @@ async fn request_check_existing_data_ids<M>(middleware: &M, mut message: Message
     let message_ref = message.message.parse()?;
     let request: CheckExistingDataIdsRequest = message_ref.contenu()?.deserialize()?;
 
-    let filtre = doc! {"feed_id": &request.feed_id, "data_id": {"$in": &request.data_ids}};
-    let collection = middleware.get_collection_typed::<DataCollectorRowIds>(COLLECTION_NAME_DATA_DATACOLLECTOR)?;
-    let mut cursor = collection.find(filtre, None).await?;
+    let collection_names = match request.version {
+        Some(1) => vec![COLLECTION_NAME_DATA_DATACOLLECTOR],
+        Some(2) => vec![COLLECTION_NAME_SRC_DATAFILES],
+        None => vec![COLLECTION_NAME_DATA_DATACOLLECTOR, COLLECTION_NAME_SRC_DATAFILES],
+        Some(_) => return Ok(Some(middleware.reponse_err(Some(400), None, Some("Unsupported version"))?))
+    };
 
     let mut present_ids = Vec::with_capacity(request.data_ids.len());
     let mut missing_ids = HashSet::with_capacity(request.data_ids.len());
     missing_ids.extend(request.data_ids);
-    while cursor.advance().await? {
-        let row = cursor.deserialize_current()?;
-        let data_id = row.data_id.to_string();
-        if missing_ids.remove(&data_id) {
-            present_ids.push(data_id);
+
+    for collection_name in collection_names {
+        if missing_ids.is_empty() {
+            break;  // All ids found
+        }
+        let filtre = doc! {"feed_id": &request.feed_id, "data_id": {"$in": missing_ids.iter().cloned().collect::<Vec<String>>()}};
+        let collection = middleware.get_collection_typed::<DataCollectorRowIds>(collection_name)?;
+        let mut cursor = collection.find(filtre, None).await?;
+        while cursor.advance().await? {
+            let row = cursor.deserialize_current()?;
+            let data_id = row.data_id.to_string();
+            if missing_ids.remove(&data_id) {
+                present_ids.push(data_id);
+            }
         }
     }
 
@@ -331,10 +457,11 @@ where M: GenerateurMessages + MongoDao + ValidateurX509
         let filtre = if is_admin {
             doc! {"user_id": null, "feed_id": &request.feed_id, "deleted": false}  // Only fetch system feeds
         } else {
-            // Regular private user, only load user feeds.
+            // Regular private user, only load user feeds, shared feeds and private system feeds.
             doc!(
                 "$or": [
-                    {"user_id": user_id},
+                    {"user_id": &user_id},
+                    {"acl.user_id": &user_id},
                     {"user_id": null, "security_level": {"$in": [SECURITE_1_PUBLIC, SECURITE_2_PRIVE]}},
                 ],
                 "feed_id": &request.feed_id,
@@ -467,6 +594,75 @@ where M: GenerateurMessages + MongoDao + ValidateurX509
     Ok(Some(middleware.build_reponse(response)?.0))
 }
 
+#[derive(Deserialize)]
+struct RequestGetDataItemsV1ToMigrate {
+    /// Feed to migrate.
+    feed_id: String,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct RequestGetDataItemsV1ToMigrateResponse {
+    ok: bool,
+    items: Vec<DataCollectorItemResponse>,
+    keys: Option<MessageMilleGrillesOwned>,
+    /// Number of V1 items left to migrate, including this batch.
+    remaining: u64,
+}
+
+/// Returns the next batch of V1 data items to migrate to V2. Migrated items are removed from V1,
+/// the remaining V1 items are the migration checkpoint.
+async fn request_get_data_items_v1_to_migrate<M>(middleware: &M, mut message: MessageValide)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    // Migration is run by an administrator or a protected component.
+    let is_admin = message.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
+    let is_protected = message.certificat.verifier_exchanges(vec![Securite::L3Protege, Securite::L4Secure])?;
+    if ! is_admin && ! is_protected {
+        return Ok(Some(middleware.reponse_err(Some(401), None, Some("Access denied"))?));
+    }
+
+    let request: RequestGetDataItemsV1ToMigrate = {
+        let message_ref = message.message.parse()?;
+        message_ref.contenu()?.deserialize()?
+    };
+
+    let filtre = doc!{"feed_id": &request.feed_id};
+
+    let limit = request.limit.unwrap_or(50).clamp(1, 500);
+    let options = FindOptions::builder()
+        .sort(doc!{"pub_date": 1, "data_id": 1})
+        .limit(limit)
+        .build();
+    let collection = middleware.get_collection_typed::<DataCollectorRow>(COLLECTION_NAME_DATA_DATACOLLECTOR)?;
+    let mut cursor = collection.find(filtre.clone(), Some(options)).await?;
+
+    let mut items: Vec<DataCollectorItemResponse> = Vec::with_capacity(limit as usize);
+    let mut key_ids = HashSet::new();
+    while cursor.advance().await? {
+        let row = cursor.deserialize_current()?;
+        if let Some(cle_id) = row.encrypted_data.cle_id.clone() {
+            key_ids.insert(cle_id);
+        }
+        if let Some(files) = row.files.as_ref() {
+            for file in files {
+                if let Some(cle_id) = file.decryption.as_ref().and_then(|d| d.cle_id.as_ref()) {
+                    key_ids.insert(cle_id.to_owned());
+                }
+            }
+        }
+        items.push(row.into());
+    }
+
+    let remaining = collection.count_documents(filtre, None).await?;
+
+    let mut response = RequestGetDataItemsV1ToMigrateResponse { ok: true, items, keys: None, remaining };
+    response.keys = fetch_decryption_keys(middleware, &message, key_ids).await?;
+
+    Ok(Some(middleware.build_reponse(response)?.0))
+}
+
 #[derive(Deserialize)]
 struct RequestGetFuuidsVolatile {correlations: Vec<String>}
 
@@ -511,6 +707,8 @@ struct FeedDataRequest {
     skip: Option<u64>,
     /// Maximum number of records to fetch at once
     limit: Option<i64>,
+    /// Continue after the last record of the previous batch (next_cursor of the response).
+    cursor: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -555,6 +753,8 @@ struct FeedDataResponse {
     ok: bool,
     items: Vec<DataCollectorFilesResponse>,
     keys: Option<MessageMilleGrillesOwned>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
 }
 
 async fn request_feed_data<M>(middleware: &M, mut message: MessageValide)
@@ -573,22 +773,32 @@ async fn request_feed_data<M>(middleware: &M, mut message: MessageValide)
         message_ref.contenu()?.deserialize()?
     };
 
-    let filtre = doc!{
+    let mut filtre = doc!{
         "save_date": {"$gt": &request.batch_start},
         "feed_id": &request.feed_id,
     };
+    if let Some(cursor) = request.cursor.as_ref() {
+        let (save_date, data_id) = match decode_cursor(cursor.as_str()) {
+            Ok((Some(save_date), data_id)) => (save_date, data_id),
+            _ => return Ok(Some(middleware.reponse_err(Some(400), None, Some("Invalid cursor"))?))
+        };
+        filtre.insert("$or", vec![
+            doc!{"save_date": {"$gt": &save_date}},
+            doc!{"save_date": &save_date, "data_id": {"$gt": &data_id}},
+        ]);
+    }
     let limit = request.limit.unwrap_or(50);
     let options = FindOptions::builder()
         .limit(limit)
         .skip(request.skip.unwrap_or(0))
-        .hint(Hint::Name("date_feed".into()))
-        // .sort(doc! {"save_date": 1})  // Implicit with index hint
+        .sort(doc! {"save_date": 1, "data_id": 1})
+        .hint(Hint::Name("feed_savedate_dataid".into()))
         .build();
     let collection = middleware.get_collection_typed::<DataCollectorFilesRow>(COLLECTION_NAME_SRC_DATAFILES)?;
     let mut cursor = collection.find(filtre, options).await?;
     
     let mut key_ids = HashSet::with_capacity(20);
-    let mut rows = Vec::with_capacity(limit as usize);
+    let mut rows: Vec<DataCollectorFilesResponse> = Vec::with_capacity(limit as usize);
     while cursor.advance().await? {
         let row = cursor.deserialize_current()?;
         // Extract key_ids
@@ -596,7 +806,13 @@ async fn request_feed_data<M>(middleware: &M, mut message: MessageValide)
         rows.push(row.into());
     }
 
-    let mut response = FeedDataResponse {ok: true, items: rows, keys: None};
+    // A full batch may have more rows
+    let next_cursor = match rows.last() {
+        Some(last) if rows.len() as i64 >= limit => Some(encode_cursor(Some(&last.save_date), last.data_id.as_str())),
+        _ => None
+    };
+
+    let mut response = FeedDataResponse {ok: true, items: rows, keys: None, next_cursor};
 
     debug!("Fetching key_ids: {:?}", key_ids);
     response.keys = fetch_decryption_keys(middleware, &message, key_ids).await?;
@@ -619,12 +835,32 @@ pub struct FeedViewResponse {
     pub name: Option<String>,
     pub active: bool,
     pub decrypted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_type: Option<String>,
     pub mapping_code: String,
     #[serde(with="epochseconds")]
     pub creation_date: DateTime<Utc>,
     #[serde(with="epochseconds")]
     pub modification_date: DateTime<Utc>,
     pub deleted: bool,
+    /// save_date of the most recent source data item requested for processing.
+    #[serde(default, with="optionepochseconds", skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<DateTime<Utc>>,
+    pub ready: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub processing_status: Option<FeedViewProcessingStatus>,
+    #[serde(default, with="optionepochseconds", skip_serializing_if = "Option::is_none")]
+    pub processing_start_date: Option<DateTime<Utc>>,
+    #[serde(default, with="optionepochseconds", skip_serializing_if = "Option::is_none")]
+    pub processing_end_date: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub processed_items: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_items: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_batch: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub processing_error: Option<String>,
 }
 
 impl From<FeedViewRow> for FeedViewResponse {
@@ -636,10 +872,20 @@ impl From<FeedViewRow> for FeedViewResponse {
             name: value.name,
             active: value.active,
             decrypted: value.decrypted,
+            data_type: value.data_type,
             mapping_code: value.mapping_code,
             creation_date: value.creation_date,
             modification_date: value.modification_date,
             deleted: value.deleted,
+            watermark: value.watermark,
+            ready: value.ready,
+            processing_status: value.processing_status,
+            processing_start_date: value.processing_start_date,
+            processing_end_date: value.processing_end_date,
+            processed_items: value.processed_items,
+            total_items: value.total_items,
+            current_batch: value.current_batch,
+            processing_error: value.processing_error,
         }
     }
 }
@@ -695,15 +941,32 @@ where M: GenerateurMessages + MongoDao + ValidateurX509
     Ok(Some(middleware.build_reponse_chiffree(response_message, message.certificat.as_ref())?.0))
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SortDirection {
+    Asc,
+    Desc,
+}
+
 #[derive(Deserialize)]
 struct FeedViewDataRequest {
     feed_view_id: String,
     skip: Option<u64>,
     limit: Option<i64>,
+    /// Continue after the last item of the previous page (next_cursor of the response).
+    cursor: Option<String>,
     #[serde(default, with="optionepochseconds")]
     start_date: Option<DateTime<Utc>>,
     #[serde(default, with="optionepochseconds")]
     end_date: Option<DateTime<Utc>>,
+    /// Only return items of these groups.
+    group_ids: Option<Vec<String>>,
+    /// Only return these items.
+    data_ids: Option<Vec<String>>,
+    /// true returns only the items with files, false only the items without files.
+    has_files: Option<bool>,
+    /// Sort by date, newest first (desc) by default. Use asc to replay the items in order.
+    sort: Option<SortDirection>,
 }
 
 #[derive(Serialize)]
@@ -714,6 +977,8 @@ struct FeedViewDatedGroupedDataResponse {
     estimated_count: u64,
     items: Vec<FeedViewGroupedDatedItem>,
     keys: Option<MessageMilleGrillesOwned>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
 }
 
 async fn request_view_data<M>(middleware: &M, mut message: MessageValide)
@@ -732,14 +997,14 @@ async fn request_view_data<M>(middleware: &M, mut message: MessageValide)
         None => return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed view"))?))
     };
 
-    let data_type = match feed_view.data_type.as_ref() {
-        Some(data_type) => ViewDataType::try_from(data_type.as_str())?,
-        None => ViewDataType::GroupedDated,  // Default to grouped-dated
-    };
+    let data_type = feed_view.get_data_type()?;
+    let data_collection_name = data_type.get_collection_name();
 
-    let (data_collection_name, hint) = match data_type {
-        ViewDataType::Dated => (COLLECTION_NAME_FEED_VIEW_DATED, Hint::Name("pubdate_desc".to_string())),
-        ViewDataType::GroupedDated => (COLLECTION_NAME_FEED_VIEW_GROUPED_DATED, Hint::Name("pubdate_desc".to_string()))
+    // Name of the date field used to filter and sort the view data
+    let (date_field, hint) = match data_type {
+        ViewDataType::TimeSeries => ("timestamp", Hint::Name("view_generation_timestamp_dataid".to_string())),
+        ViewDataType::Dated | ViewDataType::GroupedDated | ViewDataType::Keyed =>
+            ("pub_date", Hint::Name("view_generation_pubdate_dataid".to_string()))
     };
 
     // Throws Err if unauthorized
@@ -754,36 +1019,92 @@ async fn request_view_data<M>(middleware: &M, mut message: MessageValide)
         key_ids.insert(cle_id.to_owned());
     }
 
-    let mut data_filtre = doc!{"feed_view_id": &request.feed_view_id};
+    // Readers only see the active generation, a rebuild in progress is not visible.
+    let mut data_filtre = doc!{"feed_view_id": &request.feed_view_id, "generation": feed_view.generation};
+    let mut conditions = Vec::new();
 
-    // Apply date filtre when appropriate - "$and": [start, end]
-    match (request.start_date, request.end_date) {
-        (Some(start_date), Some(end_date)) => {
-            data_filtre.insert("$and",
-            vec![
-                    doc!{"pub_date": {"$gte": start_date}},
-                    doc!{"pub_date": {"$lt": end_date}}
-                ]
-            );
-        },
-        _ => ()
+    // Half-open date range [start_date, end_date), either bound is optional.
+    if let Some(start_date) = request.start_date {
+        conditions.push(doc!{date_field: {"$gte": start_date}});
+    }
+    if let Some(end_date) = request.end_date {
+        conditions.push(doc!{date_field: {"$lt": end_date}});
+    }
+
+    if let Some(group_ids) = request.group_ids.as_ref() {
+        data_filtre.insert("group_id", doc!{"$in": group_ids});
+    }
+    if let Some(data_ids) = request.data_ids.as_ref() {
+        data_filtre.insert("data_id", doc!{"$in": data_ids});
+    }
+    if let Some(has_files) = request.has_files {
+        data_filtre.insert("files.0", doc!{"$exists": has_files});
+    }
+
+    // Count items (for pagination), before applying the cursor
+    let mut count_filtre = data_filtre.clone();
+    if !conditions.is_empty() {
+        count_filtre.insert("$and", conditions.clone());
+    }
+
+    // Items without a date come last when sorting newest first and first when sorting oldest first.
+    let ascending = matches!(request.sort, Some(SortDirection::Asc));
+    let (direction, op_after) = match ascending {
+        true => (1, "$gt"),
+        false => (-1, "$lt"),
     };
+    if let Some(cursor) = request.cursor.as_ref() {
+        match decode_cursor(cursor.as_str()) {
+            Ok((Some(pub_date), data_id)) => {
+                let mut after = vec![
+                    doc!{date_field: {op_after: &pub_date}},
+                    doc!{date_field: &pub_date, "data_id": {op_after: &data_id}},
+                ];
+                if !ascending {
+                    after.push(doc!{date_field: null});
+                }
+                conditions.push(doc!{"$or": after});
+            },
+            Ok((None, data_id)) => {
+                let mut after = vec![
+                    doc!{date_field: null, "data_id": {op_after: &data_id}},
+                ];
+                if ascending {
+                    after.push(doc!{date_field: {"$ne": null}});
+                }
+                conditions.push(doc!{"$or": after});
+            },
+            Err(_) => return Ok(Some(middleware.reponse_err(Some(400), None, Some("Invalid cursor"))?))
+        }
+    }
+    if !conditions.is_empty() {
+        data_filtre.insert("$and", conditions);
+    }
 
     let skip = request.skip.unwrap_or(0);
-    let collection = middleware.get_collection_typed::<FeedViewGroupedDatedRow>(data_collection_name)?;
+    let collection = middleware.get_collection(data_collection_name)?;
 
-    // Count items (for pagination)
     let options = CountOptions::builder().limit(1000).hint(hint.clone()).build();
-    let count = collection.count_documents(data_filtre.clone(), options).await?;
+    let count = collection.count_documents(count_filtre, options).await?;
 
     // Fetch data items
-    let options = FindOptions::builder().limit(limit).skip(skip).hint(hint).build();
+    let options = FindOptions::builder()
+        .limit(limit)
+        .skip(skip)
+        .sort(doc!{date_field: direction, "data_id": direction})
+        .hint(hint)
+        .build();
     let mut cursor = collection.find(data_filtre, options).await?;
     let mut items: Vec<FeedViewGroupedDatedItem> = Vec::with_capacity(limit as usize);
 
     // Extract all key_ids
     while cursor.advance().await? {
         let row = cursor.deserialize_current()?;
+        let row: FeedViewGroupedDatedItem = match data_type {
+            ViewDataType::TimeSeries => convertir_bson_deserializable::<FeedViewTimeSeriesRow>(row)?.into(),
+            ViewDataType::Dated | ViewDataType::GroupedDated | ViewDataType::Keyed =>
+                convertir_bson_deserializable::<FeedViewGroupedDatedRow>(row)?.into()
+        };
         if let Some(cle_id) = row.encrypted_data.cle_id.as_ref() {
             key_ids.insert(cle_id.to_owned());
         }
@@ -796,10 +1117,17 @@ async fn request_view_data<M>(middleware: &M, mut message: MessageValide)
                 }
             }
         }
-        items.push(row.into());
+        items.push(row);
     }
+
+    // A full page may have more items
+    let next_cursor = match items.last() {
+        Some(last) if items.len() as i64 >= limit => Some(encode_cursor(last.pub_date.as_ref(), last.data_id.as_str())),
+        _ => None
+    };
+
     let mut response_message = FeedViewDatedGroupedDataResponse {
-        ok: true, feed: feed.into(), feed_view: feed_view.into(), estimated_count: count, items, keys: None};
+        ok: true, feed: feed.into(), feed_view: feed_view.into(), estimated_count: count, items, keys: None, next_cursor};
 
     if key_ids.len() > 0 {
         response_message.keys = fetch_decryption_keys(middleware, &message, key_ids).await?;
@@ -808,6 +1136,540 @@ async fn request_view_data<M>(middleware: &M, mut message: MessageValide)
     Ok(Some(middleware.build_reponse_chiffree(response_message, message.certificat.as_ref())?.0))
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum AggregateInterval {
+    Hour,
+    Day,
+    Week,
+}
+
+impl AggregateInterval {
+    /// Unit of the $dateTrunc operator.
+    fn as_unit(&self) -> &'static str {
+        match self {
+            Self::Hour => "hour",
+            Self::Day => "day",
+            Self::Week => "week",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct FeedViewAggregatesRequest {
+    feed_view_id: String,
+    /// Plaintext field of the decrypted data to aggregate, e.g. "temperature" or "wind.speed".
+    field: String,
+    interval: AggregateInterval,
+    /// Computes separate buckets for each group_id.
+    per_group: Option<bool>,
+    /// Timezone of the buckets (e.g. America/Toronto), UTC when missing.
+    timezone: Option<String>,
+    #[serde(default, with="optionepochseconds")]
+    start_date: Option<DateTime<Utc>>,
+    #[serde(default, with="optionepochseconds")]
+    end_date: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+struct FeedViewAggregateRow {
+    #[serde(with="millegrilles_common_rust::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    date: DateTime<Utc>,
+    group_id: Option<String>,
+    count: u64,
+    min: Option<f64>,
+    max: Option<f64>,
+    avg: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct FeedViewAggregateBucket {
+    /// Start of the bucket
+    #[serde(with="epochseconds")]
+    date: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group_id: Option<String>,
+    count: u64,
+    min: Option<f64>,
+    max: Option<f64>,
+    avg: Option<f64>,
+}
+
+impl From<FeedViewAggregateRow> for FeedViewAggregateBucket {
+    fn from(value: FeedViewAggregateRow) -> Self {
+        Self {
+            date: value.date,
+            group_id: value.group_id,
+            count: value.count,
+            min: value.min,
+            max: value.max,
+            avg: value.avg,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FeedViewAggregatesResponse {
+    ok: bool,
+    feed_view_id: String,
+    /// Buckets sorted by date, then group_id.
+    buckets: Vec<FeedViewAggregateBucket>,
+}
+
+async fn request_view_aggregates<M>(middleware: &M, message: MessageValide)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+    where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    let request: FeedViewAggregatesRequest = {
+        let message_ref = message.message.parse()?;
+        message_ref.contenu()?.deserialize()?
+    };
+
+    // The field name is used in a field path, reject operators and empty path elements.
+    let field_valid = request.field.split('.')
+        .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+    if !field_valid {
+        return Ok(Some(middleware.reponse_err(Some(400), None, Some("Invalid field"))?))
+    }
+
+    let filtre_view = doc!{"feed_view_id": &request.feed_view_id, "deleted": false};
+    let collection_views = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+    let feed_view = match collection_views.find_one(filtre_view, None).await? {
+        Some(view) => view,
+        None => return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed view"))?))
+    };
+
+    // Only decrypted views have plaintext data in the database
+    if !feed_view.decrypted {
+        return Ok(Some(middleware.reponse_err(Some(400), None, Some("View is not decrypted"))?))
+    }
+    let data_type = feed_view.get_data_type()?;
+    match data_type {
+        ViewDataType::Dated | ViewDataType::GroupedDated => (),
+        ViewDataType::Keyed | ViewDataType::TimeSeries =>
+            return Ok(Some(middleware.reponse_err(Some(400), None, Some("Unsupported view data type"))?))
+    }
+
+    // Throws Err if unauthorized
+    verify_authorized_feed(middleware, feed_view.feed_id.as_str(), message.certificat.as_ref(), true).await?;
+
+    let field_path = format!("decrypted_data.{}", request.field);
+    let value_path = format!("${}", field_path);
+
+    // Half-open date range [start_date, end_date), items without pub_date are ignored.
+    let mut date_filtre = doc!{"$ne": null};
+    if let Some(start_date) = request.start_date {
+        date_filtre.insert("$gte", start_date);
+    }
+    if let Some(end_date) = request.end_date {
+        date_filtre.insert("$lt", end_date);
+    }
+    let mut filtre = doc!{"feed_view_id": &request.feed_view_id, "generation": feed_view.generation, "pub_date": date_filtre};
+    filtre.insert(field_path.as_str(), doc!{"$type": "number"});
+
+    let mut date_trunc = doc!{"date": "$pub_date", "unit": request.interval.as_unit()};
+    if let AggregateInterval::Week = request.interval {
+        date_trunc.insert("startOfWeek", "monday");
+    }
+    if let Some(timezone) = request.timezone.as_ref() {
+        date_trunc.insert("timezone", timezone.as_str());
+    }
+    let mut group_id = doc!{"date": {"$dateTrunc": date_trunc}};
+    if Some(true) == request.per_group {
+        group_id.insert("group_id", "$group_id");
+    }
+
+    let pipeline = vec![
+        doc!{"$match": filtre},
+        doc!{"$group": {
+            "_id": group_id,
+            "count": {"$sum": 1},
+            "min": {"$min": &value_path},
+            "max": {"$max": &value_path},
+            "avg": {"$avg": &value_path},
+        }},
+        doc!{"$project": {"_id": 0, "date": "$_id.date", "group_id": "$_id.group_id", "count": 1, "min": 1, "max": 1, "avg": 1}},
+        doc!{"$sort": {"date": 1, "group_id": 1}},
+        doc!{"$limit": FEED_VIEW_AGGREGATES_MAX_BUCKETS},
+    ];
+
+    let collection = middleware.get_collection(data_type.get_collection_name())?;
+    let mut cursor = collection.aggregate(pipeline, None).await?;
+    let mut buckets = Vec::new();
+    while cursor.advance().await? {
+        let row = cursor.deserialize_current()?;
+        let row: FeedViewAggregateRow = convertir_bson_deserializable(row)?;
+        buckets.push(row.into());
+    }
+
+    let response = FeedViewAggregatesResponse { ok: true, feed_view_id: request.feed_view_id, buckets };
+
+    Ok(Some(middleware.build_reponse_chiffree(response, message.certificat.as_ref())?.0))
+}
+
+#[derive(Deserialize)]
+struct FeedViewGroupsRequest {
+    feed_view_id: String,
+    skip: Option<u64>,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct FeedViewGroupRow {
+    group_id: Option<String>,
+    count: u64,
+    #[serde(default, with="opt_chrono_datetime_as_bson_datetime")]
+    latest_pub_date: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct FeedViewGroupResponse {
+    group_id: Option<String>,
+    /// Number of items in the group
+    count: u64,
+    #[serde(with="optionepochseconds", skip_serializing_if = "Option::is_none")]
+    latest_pub_date: Option<DateTime<Utc>>,
+}
+
+impl From<FeedViewGroupRow> for FeedViewGroupResponse {
+    fn from(value: FeedViewGroupRow) -> Self {
+        Self {
+            group_id: value.group_id,
+            count: value.count,
+            latest_pub_date: value.latest_pub_date,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FeedViewGroupsResponse {
+    ok: bool,
+    feed_view_id: String,
+    /// Groups sorted by most recent pub_date first.
+    groups: Vec<FeedViewGroupResponse>,
+}
+
+/// Lists the distinct groups of a view with their item count and latest pub_date.
+async fn request_view_groups<M>(middleware: &M, message: MessageValide)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+    where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    let request: FeedViewGroupsRequest = {
+        let message_ref = message.message.parse()?;
+        message_ref.contenu()?.deserialize()?
+    };
+
+    let filtre_view = doc!{"feed_view_id": &request.feed_view_id, "deleted": false};
+    let collection_views = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+    let feed_view = match collection_views.find_one(filtre_view, None).await? {
+        Some(view) => view,
+        None => return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed view"))?))
+    };
+
+    let data_type = feed_view.get_data_type()?;
+    match data_type {
+        ViewDataType::GroupedDated | ViewDataType::Keyed => (),
+        ViewDataType::Dated | ViewDataType::TimeSeries =>
+            return Ok(Some(middleware.reponse_err(Some(400), None, Some("Unsupported view data type"))?))
+    }
+
+    // Throws Err if unauthorized
+    verify_authorized_feed(middleware, feed_view.feed_id.as_str(), message.certificat.as_ref(), true).await?;
+
+    let skip = request.skip.unwrap_or(0) as i64;
+    let limit = request.limit.unwrap_or(100).clamp(1, 1000);
+
+    let pipeline = vec![
+        doc!{"$match": {"feed_view_id": &request.feed_view_id, "generation": feed_view.generation}},
+        doc!{"$group": {"_id": "$group_id", "count": {"$sum": 1}, "latest_pub_date": {"$max": "$pub_date"}}},
+        doc!{"$project": {"_id": 0, "group_id": "$_id", "count": 1, "latest_pub_date": 1}},
+        doc!{"$sort": {"latest_pub_date": -1, "group_id": 1}},
+        doc!{"$skip": skip},
+        doc!{"$limit": limit},
+    ];
+
+    let collection = middleware.get_collection(data_type.get_collection_name())?;
+    let options = AggregateOptions::builder().allow_disk_use(true).build();
+    let mut cursor = collection.aggregate(pipeline, options).await?;
+    let mut groups = Vec::new();
+    while cursor.advance().await? {
+        let row = cursor.deserialize_current()?;
+        let row: FeedViewGroupRow = convertir_bson_deserializable(row)?;
+        groups.push(row.into());
+    }
+
+    let response = FeedViewGroupsResponse { ok: true, feed_view_id: request.feed_view_id, groups };
+
+    Ok(Some(middleware.build_reponse_chiffree(response, message.certificat.as_ref())?.0))
+}
+
+#[derive(Serialize)]
+struct FeedTransferResponse {
+    feed_id: String,
+    /// Current owner of the feed, None for a system feed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from_user_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct FeedTransfersResponse {
+    ok: bool,
+    transfers: Vec<FeedTransferResponse>,
+}
+
+/// Lists the feeds offered to the user. The feed information is only available once the transfer is accepted.
+async fn request_get_feed_transfers<M>(middleware: &M, message: MessageValide)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+    where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    let user_id = match message.certificat.get_user_id() {
+        Ok(inner) => match inner {
+            Some(user) => user.to_owned(),
+            None => {
+                error!("request_get_feed_transfers Invalid certificate, no user_id - request rejected");
+                return Ok(Some(middleware.reponse_err(Some(401), None, Some("Invalid certificate"))?));
+            }
+        },
+        Err(e) => Err(format!("request_get_feed_transfers Error get_user_id() : {:?}", e))?
+    };
+
+    let filtre = doc!{"transfer_user_id": &user_id, "deleted": false};
+    let options = FindOptions::builder().projection(doc!{"feed_id": 1, "user_id": 1}).build();
+    let collection = middleware.get_collection(COLLECTION_NAME_FEEDS)?;
+    let mut cursor = collection.find(filtre, options).await?;
+    let mut transfers = Vec::new();
+    while cursor.advance().await? {
+        let row = cursor.deserialize_current()?;
+        let feed_id = match row.get_str("feed_id") {
+            Ok(inner) => inner.to_owned(),
+            Err(_) => continue
+        };
+        let from_user_id = row.get_str("user_id").ok().map(|u| u.to_owned());
+        transfers.push(FeedTransferResponse { feed_id, from_user_id });
+    }
+
+    let response = FeedTransfersResponse { ok: true, transfers };
+
+    Ok(Some(middleware.build_reponse(response)?.0))
+}
+
+#[derive(Deserialize)]
+struct FeedViewGroupsLatestRequest {
+    feed_view_id: String,
+    /// Only return these groups, all groups when missing.
+    group_ids: Option<Vec<String>>,
+    /// Number of items to return for each group, 1 by default.
+    limit_per_group: Option<i64>,
+    /// Maximum number of groups, 50 by default. The groups with the most recent items come first.
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct FeedViewGroupLatestRow {
+    group_id: Option<String>,
+    items: Vec<FeedViewGroupedDatedRow>,
+}
+
+#[derive(Serialize)]
+struct FeedViewGroupLatestResponse {
+    group_id: Option<String>,
+    /// Most recent items first
+    items: Vec<FeedViewGroupedDatedItem>,
+}
+
+#[derive(Serialize)]
+struct FeedViewGroupsLatestResponse {
+    ok: bool,
+    feed_view_id: String,
+    groups: Vec<FeedViewGroupLatestResponse>,
+    keys: Option<MessageMilleGrillesOwned>,
+}
+
+/// Returns the latest items of each group of a view.
+async fn request_view_groups_latest<M>(middleware: &M, message: MessageValide)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+    where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    let request: FeedViewGroupsLatestRequest = {
+        let message_ref = message.message.parse()?;
+        message_ref.contenu()?.deserialize()?
+    };
+
+    let filtre_view = doc!{"feed_view_id": &request.feed_view_id, "deleted": false};
+    let collection_views = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+    let feed_view = match collection_views.find_one(filtre_view, None).await? {
+        Some(view) => view,
+        None => return Ok(Some(middleware.reponse_err(Some(404), None, Some("Unknown feed view"))?))
+    };
+
+    let data_type = feed_view.get_data_type()?;
+    match data_type {
+        ViewDataType::GroupedDated | ViewDataType::Keyed => (),
+        ViewDataType::Dated | ViewDataType::TimeSeries =>
+            return Ok(Some(middleware.reponse_err(Some(400), None, Some("Unsupported view data type"))?))
+    }
+
+    // Throws Err if unauthorized
+    verify_authorized_feed(middleware, feed_view.feed_id.as_str(), message.certificat.as_ref(), true).await?;
+
+    let limit_per_group = request.limit_per_group.unwrap_or(1).clamp(1, FEED_VIEW_GROUPS_LATEST_MAX_ITEMS);
+    let limit = request.limit.unwrap_or(50).clamp(1, FEED_VIEW_GROUPS_LATEST_MAX_GROUPS);
+
+    let mut filtre = doc!{"feed_view_id": &request.feed_view_id, "generation": feed_view.generation};
+    if let Some(group_ids) = request.group_ids.as_ref() {
+        filtre.insert("group_id", doc!{"$in": group_ids});
+    }
+
+    let pipeline = vec![
+        doc!{"$match": filtre},
+        doc!{"$group": {
+            "_id": "$group_id",
+            "latest_pub_date": {"$max": "$pub_date"},
+            // Only keep the fields of the response items
+            "items": {"$topN": {"n": limit_per_group, "sortBy": {"pub_date": -1, "data_id": -1}, "output": {
+                "data_id": "$data_id", "feed_view_id": "$feed_view_id", "feed_id": "$feed_id", "pub_date": "$pub_date",
+                "encrypted_data": "$encrypted_data", "group_id": "$group_id", "files": "$files", "decrypted_data": "$decrypted_data",
+            }}},
+        }},
+        doc!{"$sort": {"latest_pub_date": -1, "_id": 1}},
+        doc!{"$limit": limit},
+        doc!{"$project": {"_id": 0, "group_id": "$_id", "items": 1}},
+    ];
+
+    let mut key_ids = HashSet::new();
+    let collection = middleware.get_collection(data_type.get_collection_name())?;
+    let options = AggregateOptions::builder().allow_disk_use(true).build();
+    let mut cursor = collection.aggregate(pipeline, options).await?;
+    let mut groups = Vec::new();
+    while cursor.advance().await? {
+        let row = cursor.deserialize_current()?;
+        let row: FeedViewGroupLatestRow = convertir_bson_deserializable(row)?;
+        let mut items: Vec<FeedViewGroupedDatedItem> = Vec::with_capacity(row.items.len());
+        for item in row.items {
+            if let Some(cle_id) = item.encrypted_data.cle_id.as_ref() {
+                key_ids.insert(cle_id.to_owned());
+            }
+            if let Some(files) = item.files.as_ref() {
+                for file in files {
+                    if let Some(decryption) = file.decryption.as_ref() {
+                        if let Some(cle_id) = decryption.cle_id.as_ref() {
+                            key_ids.insert(cle_id.to_owned());
+                        }
+                    }
+                }
+            }
+            items.push(item.into());
+        }
+        groups.push(FeedViewGroupLatestResponse { group_id: row.group_id, items });
+    }
+
+    let mut response = FeedViewGroupsLatestResponse { ok: true, feed_view_id: request.feed_view_id, groups, keys: None };
+
+    if key_ids.len() > 0 {
+        response.keys = fetch_decryption_keys(middleware, &message, key_ids).await?;
+    }
+
+    Ok(Some(middleware.build_reponse_chiffree(response, message.certificat.as_ref())?.0))
+}
+
+/// Encodes an opaque keyset pagination cursor from the sort date and data_id of the last item.
+fn encode_cursor(date: Option<&DateTime<Utc>>, data_id: &str) -> String {
+    match date {
+        Some(date) => format!("{}:{}", date.timestamp_millis(), data_id),
+        None => format!("-:{}", data_id)
+    }
+}
+
+fn decode_cursor(cursor: &str) -> Result<(Option<DateTime<Utc>>, String), CommonError> {
+    let (date, data_id) = match cursor.split_once(':') {
+        Some(inner) => inner,
+        None => Err("decode_cursor Invalid cursor format")?
+    };
+    let date = match date {
+        "-" => None,
+        _ => {
+            let date = match date.parse::<i64>() {
+                Ok(inner) => inner,
+                Err(_) => Err("decode_cursor Invalid cursor date")?
+            };
+            match Utc.timestamp_millis_opt(date).single() {
+                Some(inner) => Some(inner),
+                None => Err("decode_cursor Invalid cursor date")?
+            }
+        }
+    };
+    Ok((date, data_id.to_string()))
+}
+
+#[derive(Deserialize)]
+struct FeedStatusRequest {
+    feed_id: String,
+}
+
+#[derive(Serialize)]
+struct FeedStatusReportResponse {
+    #[serde(with="epochseconds")]
+    date: DateTime<Utc>,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    http_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    item_count: Option<u64>,
+}
+
+impl From<FeedStatusReport> for FeedStatusReportResponse {
+    fn from(value: FeedStatusReport) -> Self {
+        Self {
+            date: value.date,
+            success: value.success,
+            error: value.error,
+            http_status: value.http_status,
+            item_count: value.item_count,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FeedStatusResponse {
+    ok: bool,
+    feed_id: String,
+    /// None when no report was received for this feed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<FeedStatusSummary>,
+    /// Most recent reports, newest first.
+    history: Vec<FeedStatusReportResponse>,
+}
+
+async fn request_get_feed_status<M>(middleware: &M, message: MessageValide)
+    -> Result<Option<MessageMilleGrillesBufferDefault>, CommonError>
+where M: GenerateurMessages + MongoDao + ValidateurX509
+{
+    let request: FeedStatusRequest = {
+        let message_ref = message.message.parse()?;
+        message_ref.contenu()?.deserialize()?
+    };
+
+    // Throws Err if unauthorized
+    verify_authorized_feed(middleware, request.feed_id.as_str(), message.certificat.as_ref(), true).await?;
+
+    let filtre = doc!{"feed_id": &request.feed_id};
+    let collection = middleware.get_collection_typed::<FeedStatusRow>(COLLECTION_NAME_FEED_STATUS)?;
+    let response = match collection.find_one(filtre, None).await? {
+        Some(mut row) => {
+            let history = match row.history.take() {
+                Some(history) => history.into_iter().rev().map(|r| r.into()).collect(),
+                None => Vec::new()
+            };
+            FeedStatusResponse { ok: true, feed_id: request.feed_id, status: Some(row.into()), history }
+        },
+        None => FeedStatusResponse { ok: true, feed_id: request.feed_id, status: None, history: Vec::new() }
+    };
+
+    Ok(Some(middleware.build_reponse(response)?.0))
+}
+
 async fn verify_authorized_feed<M>(middleware: &M, feed_id: &str, certificat: &EnveloppeCertificat, include_shared: bool) -> Result<DataFeedRow, CommonError>
     where M: MongoDao
 {
@@ -832,12 +1694,13 @@ async fn verify_authorized_feed<M>(middleware: &M, feed_id: &str, certificat: &E
         if is_admin {
             doc! {"feed_id": feed_id, "user_id": null, "deleted": false}  // Only fetch system feeds
         } else if include_shared {
-            // Regular private user, only load user feeds and public/private system feeds.
+            // Regular private user, only load user feeds, shared feeds and public/private system feeds.
             doc! {
                 "feed_id": feed_id,
                 "deleted": false,
                 "$or": [
                     {"user_id": &user_id},
+                    {"acl.user_id": &user_id},
                     {"user_id": None::<&str>, "security_level": {"$in": vec![SECURITE_1_PUBLIC, SECURITE_2_PRIVE]}},
                 ],
             }
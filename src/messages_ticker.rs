@@ -10,6 +10,7 @@ use millegrilles_common_rust::error::Error as CommonError;
 use crate::constants::*;
 use crate::domain_manager::DataCollectorDomainManager;
 use crate::file_maintenance::claim_all_files;
+use crate::maintenance::{apply_retention_policies, detect_stale_feeds, process_pending_feed_views, purge_deleted_feeds, purge_view_generations, rebuild_pending_feed_views};
 
 pub async fn consume_ticker<M>(gestionnaire: &DataCollectorDomainManager, middleware: &M, trigger: &MessageCedule)
                                -> Result<(), CommonError>
@@ -29,6 +30,37 @@ where M: MiddlewareMessages + BackupStarter + MongoDao
     let minutes = date_epoch.minute();
     let hours = date_epoch.hour();
 
+    if let Err(e) = rebuild_pending_feed_views(middleware).await {
+        error!("consume_ticker Error during rebuild of feed views: {:?}", e);
+    }
+
+    if let Err(e) = process_pending_feed_views(middleware).await {
+        error!("consume_ticker Error during processing of pending feed views: {:?}", e);
+    }
+
+    if let Err(e) = purge_view_generations(middleware).await {
+        error!("consume_ticker Error during purge of replaced view generations: {:?}", e);
+    }
+
+    if minutes == 23 {
+        if let Err(e) = purge_deleted_feeds(gestionnaire, middleware).await {
+            error!("consume_ticker Error during purge of deleted feeds: {:?}", e);
+        }
+    }
+
+    if minutes % 15 == 7 {
+        if let Err(e) = detect_stale_feeds(middleware).await {
+            error!("consume_ticker Error during detection of stale feeds: {:?}", e);
+        }
+    }
+
+    // Runs before the claim of all files to release the files of expired items.
+    if hours == 9 && minutes == 29 {
+        if let Err(e) = apply_retention_policies(gestionnaire, middleware).await {
+            error!("consume_ticker Error applying retention policies: {:?}", e);
+        }
+    }
+
     if hours == 9 && minutes == 39
     {
         if let Err(e) = claim_all_files(middleware).await {
@@ -1,7 +1,8 @@
+use log::debug;
 use millegrilles_common_rust::error::Error as CommonError;
 use millegrilles_common_rust::configuration::ConfigMessages;
 use millegrilles_common_rust::mongo_dao::{ChampIndex, IndexOptions, MongoDao};
-use crate::constants::{COLLECTION_NAME_DATA_DATACOLLECTOR, COLLECTION_NAME_FEEDS, COLLECTION_NAME_FEED_VIEWS, COLLECTION_NAME_FEED_VIEW_DATED, COLLECTION_NAME_FEED_VIEW_GROUPED_DATED, COLLECTION_NAME_SRC_DATAFILES, COLLECTION_NAME_SRC_FILES_VOLATILE};
+use crate::constants::{COLLECTION_NAME_DATA_DATACOLLECTOR, COLLECTION_NAME_FEEDS, COLLECTION_NAME_FEED_STATUS, COLLECTION_NAME_FEED_VIEWS, COLLECTION_NAME_FEED_VIEW_DATED, COLLECTION_NAME_FEED_VIEW_GROUPED_DATED, COLLECTION_NAME_FEED_VIEW_KEYED, COLLECTION_NAME_FEED_VIEW_TIME_SERIES, COLLECTION_NAME_SRC_DATAFILES, COLLECTION_NAME_SRC_FILES_VOLATILE};
 
 pub async fn prepare_mongodb_index<M>(middleware: &M) -> Result<(), CommonError>
 where M: MongoDao + ConfigMessages
@@ -20,6 +21,20 @@ where M: MongoDao + ConfigMessages
         Some(options_feeds_id)
     ).await?;
 
+    let options_feed_status_id = IndexOptions {
+        nom_index: Some(String::from("feed_status_feed_id_uniq")),
+        unique: true,
+    };
+    let champs_feed_status_id = vec!(
+        ChampIndex {nom_champ: String::from("feed_id"), direction: 1},
+    );
+    middleware.create_index(
+        middleware,
+        COLLECTION_NAME_FEED_STATUS,
+        champs_feed_status_id,
+        Some(options_feed_status_id)
+    ).await?;
+
     let options_datacollector_data_id = IndexOptions {
         nom_index: Some(String::from("datacollector_data_id_uniq")),
         unique: true,
@@ -35,6 +50,22 @@ where M: MongoDao + ConfigMessages
         Some(options_datacollector_data_id)
     ).await?;
 
+    // Latest V1 item of a feed
+    let options_datacollector_feed_pubdate = IndexOptions {
+        nom_index: Some(String::from("feed_pubdate")),
+        unique: false,
+    };
+    let champs_datacollector_feed_pubdate = vec!(
+        ChampIndex {nom_champ: String::from("feed_id"), direction: 1},
+        ChampIndex {nom_champ: String::from("pub_date"), direction: -1},
+    );
+    middleware.create_index(
+        middleware,
+        COLLECTION_NAME_DATA_DATACOLLECTOR,
+        champs_datacollector_feed_pubdate,
+        Some(options_datacollector_feed_pubdate)
+    ).await?;
+
     let options_volatile_files_id = IndexOptions {
         nom_index: Some(String::from("correlation_id_uniq")),
         unique: true,
@@ -78,6 +109,23 @@ where M: MongoDao + ConfigMessages
         Some(options_datafiles_feed_date)
     ).await?;
 
+    // Keyset pagination of the feed data
+    let options_datafiles_feed_cursor = IndexOptions {
+        nom_index: Some(String::from("feed_savedate_dataid")),
+        unique: false,
+    };
+    let champs_datafiles_feed_cursor = vec!(
+        ChampIndex {nom_champ: String::from("feed_id"), direction: 1},
+        ChampIndex {nom_champ: String::from("save_date"), direction: 1},
+        ChampIndex {nom_champ: String::from("data_id"), direction: 1},
+    );
+    middleware.create_index(
+        middleware,
+        COLLECTION_NAME_SRC_DATAFILES,
+        champs_datafiles_feed_cursor,
+        Some(options_datafiles_feed_cursor)
+    ).await?;
+
     let options_feedview_id = IndexOptions {
         nom_index: Some(String::from("feed_view_id_uniq")),
         unique: true,
@@ -93,13 +141,16 @@ where M: MongoDao + ConfigMessages
     ).await?;
 
     // view/dated
+    // Each generation of a view holds its own copy of the data items.
+    drop_index_if_exists(middleware, COLLECTION_NAME_FEED_VIEW_DATED, "data_id_uniq").await;
     let options_feedview_dated_id = IndexOptions {
-        nom_index: Some(String::from("data_id_uniq")),
+        nom_index: Some(String::from("data_id_generation_uniq")),
         unique: true,
     };
     let champs_feedview_dated_id = vec!(
         ChampIndex {nom_champ: String::from("data_id"), direction: 1},
         ChampIndex {nom_champ: String::from("feed_view_id"), direction: 1},
+        ChampIndex {nom_champ: String::from("generation"), direction: 1},
     );
     middleware.create_index(
         middleware,
@@ -123,14 +174,33 @@ where M: MongoDao + ConfigMessages
         Some(options_feedview_pubdatedesc_id)
     ).await?;
 
+    let options_feedview_dated_cursor = IndexOptions {
+        nom_index: Some(String::from("view_generation_pubdate_dataid")),
+        unique: false,
+    };
+    let champs_feedview_dated_cursor = vec!(
+        ChampIndex {nom_champ: String::from("feed_view_id"), direction: 1},
+        ChampIndex {nom_champ: String::from("generation"), direction: 1},
+        ChampIndex {nom_champ: String::from("pub_date"), direction: -1},
+        ChampIndex {nom_champ: String::from("data_id"), direction: -1},
+    );
+    middleware.create_index(
+        middleware,
+        COLLECTION_NAME_FEED_VIEW_DATED,
+        champs_feedview_dated_cursor,
+        Some(options_feedview_dated_cursor)
+    ).await?;
+
     // view/GroupedDated
+    drop_index_if_exists(middleware, COLLECTION_NAME_FEED_VIEW_GROUPED_DATED, "data_id_uniq").await;
     let options_feedview_data_id = IndexOptions {
-        nom_index: Some(String::from("data_id_uniq")),
+        nom_index: Some(String::from("data_id_generation_uniq")),
         unique: true,
     };
     let champs_feedview_data_id = vec!(
         ChampIndex {nom_champ: String::from("data_id"), direction: 1},
         ChampIndex {nom_champ: String::from("feed_view_id"), direction: 1},
+        ChampIndex {nom_champ: String::from("generation"), direction: 1},
     );
     middleware.create_index(
         middleware,
@@ -155,5 +225,141 @@ where M: MongoDao + ConfigMessages
         Some(options_feedview_pubdatedescgroup_id)
     ).await?;
 
+    let options_feedview_grouped_cursor = IndexOptions {
+        nom_index: Some(String::from("view_generation_pubdate_dataid")),
+        unique: false,
+    };
+    let champs_feedview_grouped_cursor = vec!(
+        ChampIndex {nom_champ: String::from("feed_view_id"), direction: 1},
+        ChampIndex {nom_champ: String::from("generation"), direction: 1},
+        ChampIndex {nom_champ: String::from("pub_date"), direction: -1},
+        ChampIndex {nom_champ: String::from("data_id"), direction: -1},
+    );
+    middleware.create_index(
+        middleware,
+        COLLECTION_NAME_FEED_VIEW_GROUPED_DATED,
+        champs_feedview_grouped_cursor,
+        Some(options_feedview_grouped_cursor)
+    ).await?;
+
+    // Latest items of each group
+    let options_feedview_grouped_group = IndexOptions {
+        nom_index: Some(String::from("view_generation_group_pubdate")),
+        unique: false,
+    };
+    let champs_feedview_grouped_group = vec!(
+        ChampIndex {nom_champ: String::from("feed_view_id"), direction: 1},
+        ChampIndex {nom_champ: String::from("generation"), direction: 1},
+        ChampIndex {nom_champ: String::from("group_id"), direction: 1},
+        ChampIndex {nom_champ: String::from("pub_date"), direction: -1},
+        ChampIndex {nom_champ: String::from("data_id"), direction: -1},
+    );
+    middleware.create_index(
+        middleware,
+        COLLECTION_NAME_FEED_VIEW_GROUPED_DATED,
+        champs_feedview_grouped_group,
+        Some(options_feedview_grouped_group)
+    ).await?;
+
+    // view/Keyed
+    // Only the latest item of each group is kept.
+    let options_feedview_keyed_id = IndexOptions {
+        nom_index: Some(String::from("view_generation_group_uniq")),
+        unique: true,
+    };
+    let champs_feedview_keyed_id = vec!(
+        ChampIndex {nom_champ: String::from("feed_view_id"), direction: 1},
+        ChampIndex {nom_champ: String::from("generation"), direction: 1},
+        ChampIndex {nom_champ: String::from("group_id"), direction: 1},
+    );
+    middleware.create_index(
+        middleware,
+        COLLECTION_NAME_FEED_VIEW_KEYED,
+        champs_feedview_keyed_id,
+        Some(options_feedview_keyed_id)
+    ).await?;
+
+    let options_feedview_keyed_cursor = IndexOptions {
+        nom_index: Some(String::from("view_generation_pubdate_dataid")),
+        unique: false,
+    };
+    let champs_feedview_keyed_cursor = vec!(
+        ChampIndex {nom_champ: String::from("feed_view_id"), direction: 1},
+        ChampIndex {nom_champ: String::from("generation"), direction: 1},
+        ChampIndex {nom_champ: String::from("pub_date"), direction: -1},
+        ChampIndex {nom_champ: String::from("data_id"), direction: -1},
+    );
+    middleware.create_index(
+        middleware,
+        COLLECTION_NAME_FEED_VIEW_KEYED,
+        champs_feedview_keyed_cursor,
+        Some(options_feedview_keyed_cursor)
+    ).await?;
+
+    // view/TimeSeries
+    let options_feedview_timeseries_id = IndexOptions {
+        nom_index: Some(String::from("data_id_generation_uniq")),
+        unique: true,
+    };
+    let champs_feedview_timeseries_id = vec!(
+        ChampIndex {nom_champ: String::from("data_id"), direction: 1},
+        ChampIndex {nom_champ: String::from("feed_view_id"), direction: 1},
+        ChampIndex {nom_champ: String::from("generation"), direction: 1},
+    );
+    middleware.create_index(
+        middleware,
+        COLLECTION_NAME_FEED_VIEW_TIME_SERIES,
+        champs_feedview_timeseries_id,
+        Some(options_feedview_timeseries_id)
+    ).await?;
+
+    let options_feedview_timeseries_cursor = IndexOptions {
+        nom_index: Some(String::from("view_generation_timestamp_dataid")),
+        unique: false,
+    };
+    let champs_feedview_timeseries_cursor = vec!(
+        ChampIndex {nom_champ: String::from("feed_view_id"), direction: 1},
+        ChampIndex {nom_champ: String::from("generation"), direction: 1},
+        ChampIndex {nom_champ: String::from("timestamp"), direction: -1},
+        ChampIndex {nom_champ: String::from("data_id"), direction: -1},
+    );
+    middleware.create_index(
+        middleware,
+        COLLECTION_NAME_FEED_VIEW_TIME_SERIES,
+        champs_feedview_timeseries_cursor,
+        Some(options_feedview_timeseries_cursor)
+    ).await?;
+
+    // Aggregation of the values of a series over time
+    let options_feedview_timeseries_series = IndexOptions {
+        nom_index: Some(String::from("view_generation_group_timestamp")),
+        unique: false,
+    };
+    let champs_feedview_timeseries_series = vec!(
+        ChampIndex {nom_champ: String::from("feed_view_id"), direction: 1},
+        ChampIndex {nom_champ: String::from("generation"), direction: 1},
+        ChampIndex {nom_champ: String::from("group_id"), direction: 1},
+        ChampIndex {nom_champ: String::from("timestamp"), direction: 1},
+    );
+    middleware.create_index(
+        middleware,
+        COLLECTION_NAME_FEED_VIEW_TIME_SERIES,
+        champs_feedview_timeseries_series,
+        Some(options_feedview_timeseries_series)
+    ).await?;
+
     Ok(())
 }
+
+/// Drops an index replaced by a new definition. Errors are ignored, the index is usually already gone.
+async fn drop_index_if_exists<M>(middleware: &M, collection_name: &str, index_name: &str)
+where M: MongoDao
+{
+    let collection = match middleware.get_collection(collection_name) {
+        Ok(inner) => inner,
+        Err(_) => return
+    };
+    if let Err(e) = collection.drop_index(index_name, None).await {
+        debug!("drop_index_if_exists Index {} on {} not dropped: {:?}", index_name, collection_name, e);
+    }
+}
@@ -11,6 +11,7 @@ pub fn setup_queues(manager: &DataCollectorDomainManager) -> Vec<QueueType> {
     // RK 1.public
     let requetes_publiques: Vec<&str> = vec![
         REQUEST_GET_FEEDS_FOR_SCRAPER,
+        REQUEST_GET_DUE_FEEDS_FOR_SCRAPER,
         REQUEST_CHECK_EXISTING_DATA_IDS,
         REQUEST_GET_FUUIDS_VOLATILE,
     ];
@@ -25,6 +26,13 @@ pub fn setup_queues(manager: &DataCollectorDomainManager) -> Vec<QueueType> {
         REQUEST_GET_DATA_ITEMS_MOST_RECENT,
         REQUEST_GET_DATA_ITEMS_DATE_RANGE,
         REQUEST_GET_VIEW_DATA,
+        REQUEST_GET_FEED_STATUS,
+        REQUEST_GET_VIEW_AGGREGATES,
+        REQUEST_GET_VIEW_GROUPS,
+        REQUEST_GET_VIEW_GROUPS_LATEST,
+        REQUEST_GET_FEED_TRANSFERS,
+        // Admin access (delegation globale) from the private exchange, protected components use 3.protege.
+        REQUEST_GET_DATA_ITEMS_V1_TO_MIGRATE,
     ];
     for req in requetes_privees {
         rk_volatils.push(ConfigRoutingExchange {routing_key: format!("requete.{}.{}", DOMAIN_NAME, req), exchange: Securite::L2Prive});
@@ -33,6 +41,7 @@ pub fn setup_queues(manager: &DataCollectorDomainManager) -> Vec<QueueType> {
     // RK 2.prive
     let requetes_protegees: Vec<&str> = vec![
         REQUEST_GET_FEED_DATA,
+        REQUEST_GET_DATA_ITEMS_V1_TO_MIGRATE,
     ];
     for req in requetes_protegees {
         rk_volatils.push(ConfigRoutingExchange {routing_key: format!("requete.{}.{}", DOMAIN_NAME, req), exchange: Securite::L3Protege});
@@ -41,7 +50,15 @@ pub fn setup_queues(manager: &DataCollectorDomainManager) -> Vec<QueueType> {
     let commands_public: Vec<&str> = vec![
         TRANSACTION_SAVE_DATA_ITEM,
         TRANSACTION_SAVE_DATA_ITEM_V2,
+        TRANSACTION_SAVE_DATA_ITEMS_V2,
+        TRANSACTION_UPDATE_DATA_ITEM,
+        TRANSACTION_DELETE_DATA_ITEMS,
         COMMAND_ADD_FUUIDS_VOLATILE,
+        COMMAND_ACK_FEED_POLL,
+        COMMAND_LEASE_FEEDS,
+        COMMAND_RENEW_FEED_LEASES,
+        COMMAND_RELEASE_FEED_LEASES,
+        COMMAND_REPORT_FEED_STATUS,
     ];
     for cmd in commands_public {
         rk_volatils.push(ConfigRoutingExchange {routing_key: format!("commande.{}.{}", DOMAIN_NAME, cmd), exchange: Securite::L1Public});
@@ -52,8 +69,17 @@ pub fn setup_queues(manager: &DataCollectorDomainManager) -> Vec<QueueType> {
         TRANSACTION_UPDATE_FEED,
         TRANSACTION_DELETE_FEED,
         TRANSACTION_RESTORE_FEED,
+        TRANSACTION_GRANT_FEED_ACCESS,
+        TRANSACTION_REVOKE_FEED_ACCESS,
+        TRANSACTION_TRANSFER_FEED,
+        TRANSACTION_ACCEPT_FEED_TRANSFER,
+        TRANSACTION_DELETE_DATA_ITEMS,
+        // Admin access (delegation globale) from the private exchange, protected components use 3.protege.
+        TRANSACTION_MIGRATE_DATA_ITEM_V1,
         TRANSACTION_CREATE_FEED_VIEW,
         TRANSACTION_UPDATE_FEED_VIEW,
+        TRANSACTION_DELETE_FEED_VIEW,
+        TRANSACTION_RESTORE_FEED_VIEW,
         COMMAND_PROCESS_VIEW,
     ];
     for cmd in commandes_privees {
@@ -61,12 +87,19 @@ pub fn setup_queues(manager: &DataCollectorDomainManager) -> Vec<QueueType> {
     }
 
     let commandes_protegees: Vec<&str> = vec![
+        TRANSACTION_MIGRATE_DATA_ITEM_V1,
         COMMAND_INSERT_VIEW_DATA,
+        COMMAND_PROCESS_VIEW_PROGRESS,
+        COMMAND_PROCESS_VIEW_DONE,
+        COMMAND_PROCESS_VIEW_FAILED,
     ];
     for cmd in commandes_protegees {
         rk_volatils.push(ConfigRoutingExchange {routing_key: format!("commande.{}.{}", DOMAIN_NAME, cmd), exchange: Securite::L3Protege});
     }
 
+    // Events from other domains
+    rk_volatils.push(ConfigRoutingExchange {routing_key: format!("evenement.{}.{}", DOMAIN_USER_ACCOUNTS, EVENT_USER_ACCOUNT_DELETED), exchange: Securite::L3Protege});
+
     let mut queues = Vec::new();
 
     // Queue de messages volatils (requete, commande, evenements)
@@ -1,5 +1,6 @@
+use std::collections::HashSet;
 use log::debug;
-use millegrilles_common_rust::bson::doc;
+use millegrilles_common_rust::bson::{doc, Bson, Document};
 use millegrilles_common_rust::certificats::{ValidateurX509, VerificateurPermissions};
 use millegrilles_common_rust::chrono::Utc;
 use millegrilles_common_rust::constantes::{Securite, DELEGATION_GLOBALE_PROPRIETAIRE};
@@ -7,13 +8,14 @@ use millegrilles_common_rust::db_structs::TransactionValide;
 use millegrilles_common_rust::generateur_messages::GenerateurMessages;
 use millegrilles_common_rust::mongo_dao::{convertir_to_bson, MongoDao};
 use millegrilles_common_rust::mongodb::ClientSession;
+use millegrilles_common_rust::mongodb::options::{FindOneOptions, FindOptions, UpdateOptions};
 use millegrilles_common_rust::error::Error as CommonError;
 use millegrilles_common_rust::serde_json;
 
 use crate::constants::*;
 use crate::data_mongodb::{DataCollectorFilesRow, DataCollectorRow, DataCollectorRowIds, DataFeedRow, FeedViewRow};
 use crate::domain_manager::DataCollectorDomainManager;
-use crate::transactions_struct::{CreateFeedTransaction, CreateFeedViewTransaction, DeleteFeedTransaction, SaveDataItemTransaction, SaveDataItemTransactionV2, UpdateFeedTransaction, UpdateFeedViewTransaction};
+use crate::transactions_struct::{AcceptFeedTransferTransaction, CreateFeedTransaction, CreateFeedViewTransaction, DeleteDataItem, DeleteFeedTransaction, DeleteFeedViewTransaction, ExpireFeedDataTransaction, FeedAclEntry, GrantFeedAccessTransaction, PurgeFeedTransaction, PurgeUserFeedsTransaction, RevokeFeedAccessTransaction, SaveDataItemTransaction, SaveDataItemTransactionV2, SaveDataItemsTransactionV2, TransferFeedTransaction, UpdateDataItem, UpdateFeedTransaction, UpdateFeedViewTransaction};
 
 pub async fn consume_transaction<M, T>(_gestionnaire: &DataCollectorDomainManager, middleware: &M, transaction: T, session: &mut ClientSession)
     -> Result<(), CommonError>
@@ -41,8 +43,21 @@ where
         TRANSACTION_RESTORE_FEED => transaction_restore_feed(middleware, transaction, session).await,
         TRANSACTION_SAVE_DATA_ITEM => transaction_save_data_item(middleware, transaction, session).await,
         TRANSACTION_SAVE_DATA_ITEM_V2 => transaction_save_data_item_v2(middleware, transaction, session).await,
+        TRANSACTION_SAVE_DATA_ITEMS_V2 => transaction_save_data_items_v2(middleware, transaction, session).await,
+        TRANSACTION_MIGRATE_DATA_ITEM_V1 => transaction_migrate_data_item_v1(middleware, transaction, session).await,
+        TRANSACTION_UPDATE_DATA_ITEM => transaction_update_data_item(middleware, transaction, session).await,
+        TRANSACTION_DELETE_DATA_ITEMS => transaction_delete_data_items(middleware, transaction, session).await,
         TRANSACTION_CREATE_FEED_VIEW => transaction_create_feed_view(middleware, transaction, session).await,
         TRANSACTION_UPDATE_FEED_VIEW => transaction_update_feed_view(middleware, transaction, session).await,
+        TRANSACTION_DELETE_FEED_VIEW => transaction_delete_feed_view(middleware, transaction, session).await,
+        TRANSACTION_RESTORE_FEED_VIEW => transaction_restore_feed_view(middleware, transaction, session).await,
+        TRANSACTION_PURGE_FEED => transaction_purge_feed(middleware, transaction, session).await,
+        TRANSACTION_EXPIRE_FEED_DATA => transaction_expire_feed_data(middleware, transaction, session).await,
+        TRANSACTION_GRANT_FEED_ACCESS => transaction_grant_feed_access(middleware, transaction, session).await,
+        TRANSACTION_REVOKE_FEED_ACCESS => transaction_revoke_feed_access(middleware, transaction, session).await,
+        TRANSACTION_TRANSFER_FEED => transaction_transfer_feed(middleware, transaction, session).await,
+        TRANSACTION_ACCEPT_FEED_TRANSFER => transaction_accept_feed_transfer(middleware, transaction, session).await,
+        TRANSACTION_PURGE_USER_FEEDS => transaction_purge_user_feeds(middleware, transaction, session).await,
         _ => Err(format!("core_backup.aiguillage_transaction: Transaction {} est de type non gere : {}", transaction.transaction.id, action))?
     }
 }
@@ -85,6 +100,14 @@ async fn transaction_create_feed<M>(middleware: &M, transaction: TransactionVali
         modified_at: now,
         deleted: false,
         deleted_at: None,
+        retention: transaction_create_feed.retention,
+        last_polled_at: None,
+        next_poll_at: None,
+        lease_owner: None,
+        lease_expiration: None,
+        stale: None,
+        acl: None,
+        transfer_user_id: None,
     };
 
     let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
@@ -110,12 +133,17 @@ where M: GenerateurMessages + MongoDao
 
     let poll_rate = match transaction_update_feed.poll_rate {Some(inner) => Some(inner as i64), None => None};
     let encrypted_feed_information = convertir_to_bson(transaction_update_feed.encrypted_feed_information)?;
+    let retention = match transaction_update_feed.retention {
+        Some(inner) => Some(convertir_to_bson(inner)?),
+        None => None
+    };
     let set_ops = doc! {
         "security_level": transaction_update_feed.security_level,
         "poll_rate": poll_rate,
         "active": transaction_update_feed.active,
         "decrypt_in_database": transaction_update_feed.decrypt_in_database,
         "encrypted_feed_information": encrypted_feed_information,
+        "retention": retention,
     };
     let ops = doc! {
         "$set": set_ops,
@@ -124,7 +152,13 @@ where M: GenerateurMessages + MongoDao
 
     let filtre = match is_admin {
         true => doc!{"feed_id": &transaction_update_feed.feed_id, "user_id": null},     // System feed
-        false => doc!{"feed_id": &transaction_update_feed.feed_id, "user_id": &user_id} // User feed
+        false => doc!{  // User feed, or feed shared with the manage permission
+            "feed_id": &transaction_update_feed.feed_id,
+            "$or": [
+                {"user_id": &user_id},
+                {"acl": {"$elemMatch": {"user_id": &user_id, "permission": "manage"}}},
+            ]
+        }
     };
 
     let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
@@ -153,18 +187,113 @@ where M: GenerateurMessages + MongoDao
         false => doc!{"feed_id": &transaction_delete_feed.feed_id, "user_id": &user_id},
     };
 
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+
+    if Some(true) == transaction_delete_feed.purge {
+        if collection.find_one_with_session(filtre, None, session).await?.is_none() {
+            // Feed already purged or not accessible, nothing to do.
+            debug!("transaction_delete_feed Feed {} not found, skipping purge", transaction_delete_feed.feed_id);
+            return Ok(());
+        }
+        purge_feed(middleware, transaction_delete_feed.feed_id.as_str(), session).await?;
+        return Ok(());
+    }
+
     let ops = doc! {
         "$set": {"deleted": true},
         "$currentDate": {"deleted_at": true}
     };
 
-    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
-    // collection.delete_one_with_session(filtre, None, session).await?;
     collection.update_one_with_session(filtre, ops, None, session).await?;
 
     Ok(())
 }
 
+async fn transaction_purge_feed<M>(middleware: &M, transaction: TransactionValide, session: &mut ClientSession) -> Result<(), CommonError>
+where M: GenerateurMessages + MongoDao
+{
+    // Only the domain emits this transaction, from the maintenance of deleted feeds.
+    if ! transaction.certificat.verifier_domaines(vec![DOMAIN_NAME.to_string()])? {
+        Err("transaction_purge_feed Invalid certificate, must be emitted by the domain")?;
+    }
+
+    let transaction_purge_feed: PurgeFeedTransaction = serde_json::from_str(transaction.transaction.contenu.as_str())?;
+
+    // The feed must still be deleted, it may have been restored in the meantime.
+    let filtre = doc!{"feed_id": &transaction_purge_feed.feed_id, "deleted": true};
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    if collection.find_one_with_session(filtre, None, session).await?.is_none() {
+        debug!("transaction_purge_feed Feed {} not deleted or already purged, skipping", transaction_purge_feed.feed_id);
+        return Ok(());
+    }
+
+    purge_feed(middleware, transaction_purge_feed.feed_id.as_str(), session).await
+}
+
+async fn transaction_purge_user_feeds<M>(middleware: &M, transaction: TransactionValide, session: &mut ClientSession) -> Result<(), CommonError>
+where M: GenerateurMessages + MongoDao
+{
+    // Only the domain emits this transaction, on the deletion of a user account.
+    if ! transaction.certificat.verifier_domaines(vec![DOMAIN_NAME.to_string()])? {
+        Err("transaction_purge_user_feeds Invalid certificate, must be emitted by the domain")?;
+    }
+
+    let transaction_purge: PurgeUserFeedsTransaction = serde_json::from_str(transaction.transaction.contenu.as_str())?;
+    let user_id = transaction_purge.user_id.as_str();
+
+    // Nobody can restore the feeds of a deleted account, purge them right away.
+    // The files are released on the next claim of all files.
+    let filtre = doc!{"user_id": user_id};
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let mut cursor = collection.find_with_session(filtre, None, session).await?;
+    let mut feed_ids = Vec::new();
+    while cursor.advance(session).await? {
+        let row = cursor.deserialize_current()?;
+        feed_ids.push(row.feed_id);
+    }
+
+    for feed_id in feed_ids {
+        purge_feed(middleware, feed_id.as_str(), session).await?;
+    }
+
+    // Remove the access given to this user on the feeds of others
+    let filtre = doc!{"acl.user_id": user_id};
+    let ops = doc!{"$pull": {"acl": {"user_id": user_id}}};
+    collection.update_many_with_session(filtre, ops, None, session).await?;
+
+    Ok(())
+}
+
+/// Permanently removes a feed with all its data items, views and view data.
+async fn purge_feed<M>(middleware: &M, feed_id: &str, session: &mut ClientSession) -> Result<(), CommonError>
+where M: MongoDao
+{
+    debug!("purge_feed Purging feed {}", feed_id);
+    let filtre = doc!{"feed_id": feed_id};
+
+    // Remove the dependent documents first, the feed row goes last.
+    let dependent_collections = [
+        COLLECTION_NAME_DATA_DATACOLLECTOR,
+        COLLECTION_NAME_SRC_DATAFILES,
+        COLLECTION_NAME_FEED_VIEW_DATED,
+        COLLECTION_NAME_FEED_VIEW_GROUPED_DATED,
+        COLLECTION_NAME_FEED_VIEW_KEYED,
+        COLLECTION_NAME_FEED_VIEW_TIME_SERIES,
+        COLLECTION_NAME_FEED_VIEWS,
+        COLLECTION_NAME_FEED_STATUS,
+    ];
+    for collection_name in dependent_collections {
+        let collection = middleware.get_collection(collection_name)?;
+        let result = collection.delete_many_with_session(filtre.clone(), None, session).await?;
+        debug!("purge_feed Removed {} documents from {}", result.deleted_count, collection_name);
+    }
+
+    let collection = middleware.get_collection(COLLECTION_NAME_FEEDS)?;
+    collection.delete_one_with_session(filtre, None, session).await?;
+
+    Ok(())
+}
+
 async fn transaction_save_data_item<M>(middleware: &M, transaction: TransactionValide, session: &mut ClientSession)
     -> Result<(), CommonError>
     where M: GenerateurMessages + MongoDao
@@ -203,6 +332,227 @@ where M: GenerateurMessages + MongoDao
     Ok(())
 }
 
+async fn transaction_save_data_items_v2<M>(middleware: &M, transaction: TransactionValide, session: &mut ClientSession)
+    -> Result<(), CommonError>
+where M: GenerateurMessages + MongoDao
+{
+    // Emitted by the domain with the items accepted by the command. Older transactions are signed by the scraper.
+    let is_domain = transaction.certificat.verifier_domaines(vec![DOMAIN_NAME.to_string()])?;
+    if is_domain {
+        // Ok
+    } else if ! transaction.certificat.verifier_roles_string(vec!["web_scraper".to_string()])? {
+        Err("transaction_save_data_items_v2 Invalid role")?;
+    } else if ! transaction.certificat.verifier_exchanges(vec![Securite::L1Public])? {
+        Err("transaction_save_data_items_v2 Invalid security")?;
+    }
+
+    let transaction_save_data_items: SaveDataItemsTransactionV2 = serde_json::from_str(transaction.transaction.contenu.as_str())?;
+
+    // Items of unknown or deleted feeds are skipped.
+    let feed_ids: HashSet<String> = transaction_save_data_items.items.iter().map(|item| item.feed_id.clone()).collect();
+    let filtre_feeds = doc!{"feed_id": {"$in": feed_ids.into_iter().collect::<Vec<String>>()}, "deleted": false};
+    let collection_feeds = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let mut cursor = collection_feeds.find_with_session(filtre_feeds, None, session).await?;
+    let mut valid_feed_ids = HashSet::new();
+    while cursor.advance(session).await? {
+        let row = cursor.deserialize_current()?;
+        valid_feed_ids.insert(row.feed_id);
+    }
+
+    let collection = middleware.get_collection(COLLECTION_NAME_SRC_DATAFILES)?;
+    for item in transaction_save_data_items.items {
+        if ! valid_feed_ids.contains(&item.feed_id) {
+            debug!("transaction_save_data_items_v2 Skipping data item {}, unknown feed {}", item.data_id, item.feed_id);
+            continue;
+        }
+        // Insert only when missing, duplicates are ignored.
+        let filtre = doc!{"feed_id": &item.feed_id, "data_id": &item.data_id};
+        let data_item: DataCollectorFilesRow = item.into();
+        let ops = doc!{"$setOnInsert": convertir_to_bson(data_item)?};
+        let options = UpdateOptions::builder().upsert(true).build();
+        collection.update_one_with_session(filtre, ops, options, session).await?;
+    }
+
+    Ok(())
+}
+
+/// Replaces a V1 data item (inline encrypted data) by its V2 representation (data file).
+async fn transaction_migrate_data_item_v1<M>(middleware: &M, transaction: TransactionValide, session: &mut ClientSession)
+    -> Result<(), CommonError>
+where M: GenerateurMessages + MongoDao
+{
+    let is_admin = transaction.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
+    let is_protected = transaction.certificat.verifier_exchanges(vec![Securite::L3Protege, Securite::L4Secure])?;
+    if ! is_admin && ! is_protected {
+        Err("transaction_migrate_data_item_v1 Must be emitted by an administrator or a protected component")?;
+    }
+
+    let transaction_migrate: SaveDataItemTransactionV2 = serde_json::from_str(transaction.transaction.contenu.as_str())?;
+
+    // The V1 row is only removed when the V2 row can be saved, data_id is unique across all feeds.
+    // The V2 item can be saved after the migration, this is not an error during a regeneration.
+    let collection = middleware.get_collection(COLLECTION_NAME_SRC_DATAFILES)?;
+    let filtre = doc!{"data_id": &transaction_migrate.data_id};
+    if collection.find_one_with_session(filtre, None, session).await?.is_some() {
+        debug!("transaction_migrate_data_item_v1 V2 data item {} already exists, V1 item kept", transaction_migrate.data_id);
+        return Ok(());
+    }
+
+    // Removing the V1 row in the same session keeps the migration resumable: remaining V1 rows are not migrated.
+    let filtre_v1 = doc!{"feed_id": &transaction_migrate.feed_id, "data_id": &transaction_migrate.data_id};
+    let collection_v1 = middleware.get_collection(COLLECTION_NAME_DATA_DATACOLLECTOR)?;
+    collection_v1.delete_one_with_session(filtre_v1, None, session).await?;
+
+    let data_item: DataCollectorFilesRow = transaction_migrate.into();
+    collection.insert_one_with_session(convertir_to_bson(data_item)?, None, session).await?;
+
+    Ok(())
+}
+
+async fn transaction_update_data_item<M>(middleware: &M, transaction: TransactionValide, session: &mut ClientSession)
+    -> Result<(), CommonError>
+where M: GenerateurMessages + MongoDao
+{
+    if ! transaction.certificat.verifier_roles_string(vec!["web_scraper".to_string()])? {
+        Err("transaction_update_data_item Invalid role")?;
+    } else if ! transaction.certificat.verifier_exchanges(vec![Securite::L1Public])? {
+        Err("transaction_update_data_item Invalid security")?;
+    }
+
+    let transaction_update: UpdateDataItem = serde_json::from_str(transaction.transaction.contenu.as_str())?;
+    let filtre = doc!{"feed_id": &transaction_update.feed_id, "data_id": &transaction_update.data_id};
+
+    // V2 items reference their files by fuuid, V1 items keep the full FileItem.
+    let collection_v2 = middleware.get_collection(COLLECTION_NAME_SRC_DATAFILES)?;
+    let collection_v1 = middleware.get_collection(COLLECTION_NAME_DATA_DATACOLLECTOR)?;
+    let (collection, files_field, add_files, set_ops) = if collection_v2.find_one_with_session(filtre.clone(), None, session).await?.is_some() {
+        let mut set_ops = Document::new();
+        if let Some(data_fuuid) = transaction_update.data_fuuid {
+            set_ops.insert("data_fuuid", data_fuuid);
+        }
+        if let Some(key_ids) = transaction_update.key_ids {
+            set_ops.insert("key_ids", key_ids);
+        }
+        if let Some(pub_date_start) = transaction_update.pub_date_start {
+            set_ops.insert("pub_date_start", pub_date_start);
+        }
+        if let Some(pub_date_end) = transaction_update.pub_date_end {
+            set_ops.insert("pub_date_end", pub_date_end);
+        }
+        let mut add_files: Vec<Bson> = Vec::new();
+        if let Some(files) = transaction_update.add_files {
+            for file in files {
+                add_files.push(file.fuuid.into());
+            }
+        }
+        (collection_v2, "attached_fuuids", add_files, set_ops)
+    } else if collection_v1.find_one_with_session(filtre.clone(), None, session).await?.is_some() {
+        let mut set_ops = Document::new();
+        if let Some(pub_date) = transaction_update.pub_date {
+            set_ops.insert("pub_date", pub_date);
+        }
+        if let Some(encrypted_content) = transaction_update.encrypted_content {
+            set_ops.insert("encrypted_data", convertir_to_bson(encrypted_content)?);
+        }
+        let mut add_files: Vec<Bson> = Vec::new();
+        if let Some(files) = transaction_update.add_files {
+            for file in files {
+                add_files.push(convertir_to_bson(file)?.into());
+            }
+        }
+        (collection_v1, "files", add_files, set_ops)
+    } else {
+        Err(format!("transaction_update_data_item Unknown data item {}", transaction_update.data_id))?
+    };
+
+    if ! set_ops.is_empty() {
+        collection.update_one_with_session(filtre.clone(), doc!{"$set": set_ops}, None, session).await?;
+    }
+
+    // Remove files before adding to allow replacing a file with the same fuuid.
+    if let Some(remove_files) = transaction_update.remove_files {
+        let pull_filtre = match files_field {
+            "files" => doc!{"fuuid": {"$in": remove_files}},
+            _ => doc!{"$in": remove_files},
+        };
+        let ops = doc!{"$pull": {files_field: pull_filtre}};
+        collection.update_one_with_session(filtre.clone(), ops, None, session).await?;
+    }
+
+    if ! add_files.is_empty() {
+        let ops = doc!{"$push": {files_field: {"$each": add_files}}};
+        collection.update_one_with_session(filtre, ops, None, session).await?;
+    }
+
+    Ok(())
+}
+
+async fn transaction_delete_data_items<M>(middleware: &M, transaction: TransactionValide, session: &mut ClientSession)
+    -> Result<(), CommonError>
+where M: GenerateurMessages + MongoDao
+{
+    let transaction_delete: DeleteDataItem = serde_json::from_str(transaction.transaction.contenu.as_str())?;
+
+    let is_scraper = transaction.certificat.verifier_roles_string(vec!["web_scraper".to_string()])? &&
+        transaction.certificat.verifier_exchanges(vec![Securite::L1Public])?;
+
+    let filtre_feed = if is_scraper {
+        doc!{"feed_id": &transaction_delete.feed_id}
+    } else {
+        let user_id = match transaction.certificat.get_user_id() {
+            Ok(inner) => match inner {
+                Some(user) => user.to_owned(),
+                None => Err("transaction_delete_data_items User_id missing from certificate")?
+            },
+            Err(e) => Err(format!("transaction_delete_data_items Error getting user_id: {:?}", e))?
+        };
+        let is_admin = transaction.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
+        // User feed, or feed shared with the manage permission. When admin, also operate on system feeds (user_id is null).
+        let mut access = vec![
+            doc!{"user_id": &user_id},
+            doc!{"acl": {"$elemMatch": {"user_id": &user_id, "permission": "manage"}}},
+        ];
+        if is_admin {
+            access.push(doc!{"user_id": null});
+        }
+        doc!{"feed_id": &transaction_delete.feed_id, "$or": access}
+    };
+
+    let collection_feeds = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    if collection_feeds.find_one_with_session(filtre_feed, None, session).await?.is_none() {
+        Err(format!("transaction_delete_data_items Feed {} not found / access refused", transaction_delete.feed_id))?;
+    }
+
+    // Remove the source data (both generations).
+    let filtre = doc!{"feed_id": &transaction_delete.feed_id, "data_id": {"$in": &transaction_delete.ids}};
+    let collections = [
+        COLLECTION_NAME_DATA_DATACOLLECTOR,
+        COLLECTION_NAME_SRC_DATAFILES,
+    ];
+    for collection_name in collections {
+        let collection = middleware.get_collection(collection_name)?;
+        let result = collection.delete_many_with_session(filtre.clone(), None, session).await?;
+        debug!("transaction_delete_data_items Removed {} documents from {}", result.deleted_count, collection_name);
+    }
+
+    mark_feed_views_rebuild(middleware, transaction_delete.feed_id.as_str(), session).await?;
+
+    Ok(())
+}
+
+/// Flags the views of the feed for a full rebuild after source data was removed.
+/// View rows don't always keep the data_id of their source (aggregated views, time series buckets),
+/// the rebuild replaces them with a new generation.
+async fn mark_feed_views_rebuild<M>(middleware: &M, feed_id: &str, session: &mut ClientSession) -> Result<(), CommonError>
+where M: MongoDao
+{
+    let filtre = doc!{"feed_id": feed_id, "deleted": false};
+    let ops = doc!{"$set": {"rebuild_pending": true}};
+    let collection = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+    collection.update_many_with_session(filtre, ops, None, session).await?;
+    Ok(())
+}
+
 async fn transaction_create_feed_view<M>(middleware: &M, transaction: TransactionValide, session: &mut ClientSession) -> Result<(), CommonError>
 where M: GenerateurMessages + MongoDao
 {
@@ -210,6 +560,11 @@ where M: GenerateurMessages + MongoDao
     let estampille = transaction.transaction.estampille;
     let transaction_create_feed_view: CreateFeedViewTransaction = serde_json::from_str(transaction.transaction.contenu.as_str())?;
 
+    let data_type = match transaction_create_feed_view.data_type.as_ref() {
+        Some(data_type) => Some(ViewDataType::try_from(data_type.as_str())?.as_str().to_string()),
+        None => None
+    };
+
     let now = Utc::now();
 
     let data_row = FeedViewRow {
@@ -219,12 +574,29 @@ where M: GenerateurMessages + MongoDao
         name: transaction_create_feed_view.name,
         active: transaction_create_feed_view.active,
         decrypted: transaction_create_feed_view.decrypted,
-        data_type: None,
+        data_type,
         mapping_code: transaction_create_feed_view.mapping_code,
         creation_date: estampille,
         modification_date: now,
         deleted: false,
         ready: false,
+        watermark: None,
+        pending_since: None,
+        pending_last: None,
+        processing_since: None,
+        processing_until: None,
+        processing_status: None,
+        processing_start_date: None,
+        processing_end_date: None,
+        processed_items: None,
+        total_items: None,
+        current_batch: None,
+        processing_error: None,
+        generation: None,
+        pending_generation: None,
+        last_generation: None,
+        purge_generations: None,
+        rebuild_pending: None,
     };
 
     let collection = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
@@ -239,21 +611,41 @@ where M: GenerateurMessages + MongoDao
     let transaction_update_feed_view: UpdateFeedViewTransaction = serde_json::from_str(transaction.transaction.contenu.as_str())?;
 
     let filtre = doc! {
-        "feed_view_id": transaction_update_feed_view.feed_view_id,
-        "feed_id": transaction_update_feed_view.feed_id,  // For safety (access rules)
+        "feed_view_id": &transaction_update_feed_view.feed_view_id,
+        "feed_id": &transaction_update_feed_view.feed_id,  // For safety (access rules)
     };
-    let ops = doc!{
-        "$set": {
-            "encrypted_data": convertir_to_bson(transaction_update_feed_view.encrypted_data)?,
-            "name": transaction_update_feed_view.name,
-            "active": transaction_update_feed_view.active,
-            "decrypted": transaction_update_feed_view.decrypted,
-            "mapping_code": transaction_update_feed_view.mapping_code,
-        },
+    let mut set_ops = doc!{
+        "encrypted_data": convertir_to_bson(transaction_update_feed_view.encrypted_data)?,
+        "name": transaction_update_feed_view.name,
+        "active": transaction_update_feed_view.active,
+        "decrypted": transaction_update_feed_view.decrypted,
+        "mapping_code": transaction_update_feed_view.mapping_code,
+    };
+    let mut ops = doc!{
         "$currentDate": {"modification_date": true},
     };
 
     let collection = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+
+    if let Some(data_type) = transaction_update_feed_view.data_type.as_ref() {
+        let data_type = ViewDataType::try_from(data_type.as_str())?;
+        let feed_view = match collection.find_one_with_session(filtre.clone(), None, session).await? {
+            Some(inner) => inner,
+            None => Err("transaction_update_feed_view Unknown feed view")?
+        };
+        let current_data_type = feed_view.get_data_type()?;
+        if current_data_type != data_type {
+            // The data goes to another collection, the view must be processed again.
+            let collection_data = middleware.get_collection(current_data_type.get_collection_name())?;
+            let filtre_data = doc!{"feed_view_id": &transaction_update_feed_view.feed_view_id};
+            collection_data.delete_many_with_session(filtre_data, None, session).await?;
+            set_ops.insert("data_type", data_type.as_str());
+            set_ops.insert("ready", false);
+            ops.insert("$unset", doc!{"watermark": true, "generation": true, "pending_generation": true, "processing_status": true});
+        }
+    }
+    ops.insert("$set", set_ops);
+
     let result = collection.update_one_with_session(filtre, ops, None, session).await?;
 
     if result.matched_count != 1 {
@@ -263,6 +655,215 @@ where M: GenerateurMessages + MongoDao
     Ok(())
 }
 
+async fn transaction_grant_feed_access<M>(middleware: &M, transaction: TransactionValide, session: &mut ClientSession) -> Result<(), CommonError>
+where M: GenerateurMessages + MongoDao
+{
+    let transaction_grant: GrantFeedAccessTransaction = serde_json::from_str(transaction.transaction.contenu.as_str())?;
+
+    let user_id = match transaction.certificat.get_user_id() {
+        Ok(inner) => match inner {
+            Some(user) => user.to_owned(),
+            None => Err("transaction_grant_feed_access User_id missing from certificate")?
+        },
+        Err(e) => Err(format!("transaction_grant_feed_access Error getting user_id: {:?}", e))?
+    };
+    let is_admin = transaction.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
+
+    // Only the owner can share the feed. When admin, operate on system feeds (user_id is null)
+    let filtre = match is_admin {
+        true => doc!{"feed_id": &transaction_grant.feed_id, "user_id": null},
+        false => doc!{"feed_id": &transaction_grant.feed_id, "user_id": &user_id},
+    };
+
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+
+    // Replace the existing entry for the grantee
+    let ops = doc!{"$pull": {"acl": {"user_id": &transaction_grant.user_id}}};
+    let result = collection.update_one_with_session(filtre.clone(), ops, None, session).await?;
+    if result.matched_count != 1 {
+        Err("transaction_grant_feed_access Feed not found / access refused")?;
+    }
+
+    let entry = FeedAclEntry { user_id: transaction_grant.user_id, permission: transaction_grant.permission };
+    let ops = doc!{
+        "$push": {"acl": convertir_to_bson(entry)?},
+        "$currentDate": {"modified_at": true}
+    };
+    collection.update_one_with_session(filtre, ops, None, session).await?;
+
+    Ok(())
+}
+
+async fn transaction_revoke_feed_access<M>(middleware: &M, transaction: TransactionValide, session: &mut ClientSession) -> Result<(), CommonError>
+where M: GenerateurMessages + MongoDao
+{
+    let transaction_revoke: RevokeFeedAccessTransaction = serde_json::from_str(transaction.transaction.contenu.as_str())?;
+
+    let user_id = match transaction.certificat.get_user_id() {
+        Ok(inner) => match inner {
+            Some(user) => user.to_owned(),
+            None => Err("transaction_revoke_feed_access User_id missing from certificate")?
+        },
+        Err(e) => Err(format!("transaction_revoke_feed_access Error getting user_id: {:?}", e))?
+    };
+    let is_admin = transaction.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
+
+    // When admin, operate on system feeds (user_id is null)
+    let filtre = match is_admin {
+        true => doc!{"feed_id": &transaction_revoke.feed_id, "user_id": null},
+        false => doc!{"feed_id": &transaction_revoke.feed_id, "user_id": &user_id},
+    };
+
+    let ops = doc!{
+        "$pull": {"acl": {"user_id": &transaction_revoke.user_id}},
+        "$currentDate": {"modified_at": true}
+    };
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    collection.update_one_with_session(filtre, ops, None, session).await?;
+
+    Ok(())
+}
+
+async fn transaction_transfer_feed<M>(middleware: &M, transaction: TransactionValide, session: &mut ClientSession) -> Result<(), CommonError>
+where M: GenerateurMessages + MongoDao
+{
+    let transaction_transfer: TransferFeedTransaction = serde_json::from_str(transaction.transaction.contenu.as_str())?;
+
+    let user_id = match transaction.certificat.get_user_id() {
+        Ok(inner) => match inner {
+            Some(user) => user.to_owned(),
+            None => Err("transaction_transfer_feed User_id missing from certificate")?
+        },
+        Err(e) => Err(format!("transaction_transfer_feed Error getting user_id: {:?}", e))?
+    };
+    let is_admin = transaction.certificat.verifier_delegation_globale(DELEGATION_GLOBALE_PROPRIETAIRE)?;
+
+    // Admin can offer any feed. A user can only offer one of their feeds.
+    let filtre = match is_admin {
+        true => doc!{"feed_id": &transaction_transfer.feed_id},
+        false => doc!{"feed_id": &transaction_transfer.feed_id, "user_id": &user_id}
+    };
+
+    let ops = match transaction_transfer.user_id.as_ref() {
+        // The new owner must accept the transfer (acceptFeedTransfer), the ownership does not change until then.
+        Some(new_user_id) => doc! {
+            "$set": {"transfer_user_id": new_user_id},
+            "$currentDate": {"modified_at": true}
+        },
+        // No recipient to accept a system feed, the admin promotes it immediately.
+        None => {
+            if !is_admin {
+                Err("transaction_transfer_feed Only an admin can transfer a feed to the system scope")?;
+            }
+            doc! {
+                "$set": {"user_id": None::<&str>},
+                "$unset": {"transfer_user_id": true},
+                "$currentDate": {"modified_at": true}
+            }
+        }
+    };
+
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let result = collection.update_one_with_session(filtre, ops, None, session).await?;
+    if result.matched_count != 1 {
+        Err("transaction_transfer_feed Feed not found / access refused")?;
+    }
+
+    Ok(())
+}
+
+async fn transaction_accept_feed_transfer<M>(middleware: &M, transaction: TransactionValide, session: &mut ClientSession) -> Result<(), CommonError>
+where M: GenerateurMessages + MongoDao
+{
+    let transaction_accept: AcceptFeedTransferTransaction = serde_json::from_str(transaction.transaction.contenu.as_str())?;
+
+    let user_id = match transaction.certificat.get_user_id() {
+        Ok(inner) => match inner {
+            Some(user) => user.to_owned(),
+            None => Err("transaction_accept_feed_transfer User_id missing from certificate")?
+        },
+        Err(e) => Err(format!("transaction_accept_feed_transfer Error getting user_id: {:?}", e))?
+    };
+
+    // Only the user the feed was offered to can accept the transfer.
+    let filtre = doc!{"feed_id": &transaction_accept.feed_id, "transfer_user_id": &user_id};
+    let ops = doc! {
+        "$set": {"user_id": &user_id},
+        "$unset": {"transfer_user_id": true},
+        "$pull": {"acl": {"user_id": &user_id}},  // The new owner no longer needs a shared access
+        "$currentDate": {"modified_at": true}
+    };
+
+    let collection = middleware.get_collection_typed::<DataFeedRow>(COLLECTION_NAME_FEEDS)?;
+    let result = collection.update_one_with_session(filtre, ops, None, session).await?;
+    if result.matched_count != 1 {
+        Err("transaction_accept_feed_transfer Feed not found / no transfer to this user")?;
+    }
+
+    Ok(())
+}
+
+async fn transaction_delete_feed_view<M>(middleware: &M, transaction: TransactionValide, session: &mut ClientSession) -> Result<(), CommonError>
+where M: GenerateurMessages + MongoDao
+{
+    let transaction_delete_feed_view: DeleteFeedViewTransaction = serde_json::from_str(transaction.transaction.contenu.as_str())?;
+
+    let filtre = doc! {
+        "feed_view_id": &transaction_delete_feed_view.feed_view_id,
+        "feed_id": &transaction_delete_feed_view.feed_id,  // For safety (access rules)
+    };
+    let collection = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+
+    if Some(true) == transaction_delete_feed_view.purge {
+        if collection.find_one_with_session(filtre.clone(), None, session).await?.is_none() {
+            debug!("transaction_delete_feed_view View {} already purged, skipping", transaction_delete_feed_view.feed_view_id);
+            return Ok(());
+        }
+
+        // Remove the view data of all generations, then the view.
+        let filtre_data = doc!{"feed_view_id": &transaction_delete_feed_view.feed_view_id};
+        let view_collections = [
+            COLLECTION_NAME_FEED_VIEW_DATED,
+            COLLECTION_NAME_FEED_VIEW_GROUPED_DATED,
+            COLLECTION_NAME_FEED_VIEW_KEYED,
+            COLLECTION_NAME_FEED_VIEW_TIME_SERIES,
+        ];
+        for collection_name in view_collections {
+            let collection_data = middleware.get_collection(collection_name)?;
+            let result = collection_data.delete_many_with_session(filtre_data.clone(), None, session).await?;
+            debug!("transaction_delete_feed_view Removed {} documents from {}", result.deleted_count, collection_name);
+        }
+        collection.delete_one_with_session(filtre, None, session).await?;
+    } else {
+        let ops = doc!{
+            "$set": {"deleted": true},
+            "$currentDate": {"modification_date": true},
+        };
+        collection.update_one_with_session(filtre, ops, None, session).await?;
+    }
+
+    Ok(())
+}
+
+async fn transaction_restore_feed_view<M>(middleware: &M, transaction: TransactionValide, session: &mut ClientSession) -> Result<(), CommonError>
+where M: GenerateurMessages + MongoDao
+{
+    let transaction_restore_feed_view: DeleteFeedViewTransaction = serde_json::from_str(transaction.transaction.contenu.as_str())?;
+
+    let filtre = doc! {
+        "feed_view_id": &transaction_restore_feed_view.feed_view_id,
+        "feed_id": &transaction_restore_feed_view.feed_id,  // For safety (access rules)
+    };
+    let ops = doc!{
+        "$set": {"deleted": false},
+        "$currentDate": {"modification_date": true},
+    };
+    let collection = middleware.get_collection_typed::<FeedViewRow>(COLLECTION_NAME_FEED_VIEWS)?;
+    collection.update_one_with_session(filtre, ops, None, session).await?;
+
+    Ok(())
+}
+
 async fn transaction_restore_feed<M>(middleware: &M, transaction: TransactionValide, session: &mut ClientSession) -> Result<(), CommonError>
 where M: GenerateurMessages + MongoDao
 {
@@ -293,3 +894,109 @@ where M: GenerateurMessages + MongoDao
 
     Ok(())
 }
+
+async fn transaction_expire_feed_data<M>(middleware: &M, transaction: TransactionValide, session: &mut ClientSession) -> Result<(), CommonError>
+where M: GenerateurMessages + MongoDao
+{
+    // Only the domain emits this transaction, from the maintenance of the retention policies.
+    if ! transaction.certificat.verifier_domaines(vec![DOMAIN_NAME.to_string()])? {
+        Err("transaction_expire_feed_data Invalid certificate, must be emitted by the domain")?;
+    }
+
+    let transaction_expire: ExpireFeedDataTransaction = serde_json::from_str(transaction.transaction.contenu.as_str())?;
+    let feed_id = transaction_expire.feed_id.as_str();
+
+    // V1 items are dated with pub_date, V2 items with save_date.
+    let collections = [
+        (COLLECTION_NAME_DATA_DATACOLLECTOR, "pub_date"),
+        (COLLECTION_NAME_SRC_DATAFILES, "save_date"),
+    ];
+
+    let mut expired_count = 0;
+    for (collection_name, date_field) in collections {
+        if let Some(expiration_date) = transaction_expire.expiration_date.as_ref() {
+            let filtre = doc!{"feed_id": feed_id, date_field: {"$lt": expiration_date}};
+            let deleted_count = expire_items(middleware, collection_name, date_field, feed_id, filtre, session).await?;
+            debug!("transaction_expire_feed_data Expired {} items by date from {}", deleted_count, collection_name);
+            expired_count += deleted_count;
+        }
+
+        if let Some(max_items) = transaction_expire.max_items {
+            // The first item past the newest max_items is the cutoff, it is expired with all older items.
+            // Items without a date are sorted last.
+            let options = FindOneOptions::builder()
+                .sort(doc!{date_field: -1, "data_id": -1})
+                .skip(max_items)
+                .projection(doc!{"data_id": 1, date_field: 1})
+                .build();
+            let collection = middleware.get_collection(collection_name)?;
+            let cutoff = match collection.find_one_with_session(doc!{"feed_id": feed_id}, options, session).await? {
+                Some(inner) => inner,
+                None => continue  // Not over max_items
+            };
+            let cutoff_data_id = match cutoff.get_str("data_id") {
+                Ok(inner) => inner.to_owned(),
+                Err(_) => Err("transaction_expire_feed_data Cutoff item without data_id")?
+            };
+            let filtre = match cutoff.get(date_field) {
+                Some(Bson::DateTime(cutoff_date)) => doc!{"feed_id": feed_id, "$or": [
+                    {date_field: {"$lt": *cutoff_date}},
+                    {date_field: *cutoff_date, "data_id": {"$lte": &cutoff_data_id}},
+                    {date_field: null},
+                ]},
+                _ => doc!{"feed_id": feed_id, date_field: null, "data_id": {"$lte": &cutoff_data_id}},
+            };
+            let deleted_count = expire_items(middleware, collection_name, date_field, feed_id, filtre, session).await?;
+            debug!("transaction_expire_feed_data Expired {} items by count from {}", deleted_count, collection_name);
+            expired_count += deleted_count;
+        }
+    }
+
+    if expired_count > 0 {
+        mark_feed_views_rebuild(middleware, feed_id, session).await?;
+    }
+
+    Ok(())
+}
+
+/// Removes the source items matching the filter, the views are rebuilt by the caller.
+/// Works in batches, oldest first, to keep each delete small. The number of batches is bounded,
+/// the remaining items are expired by the next maintenance run.
+async fn expire_items<M>(middleware: &M, collection_name: &str, date_field: &str, feed_id: &str, filtre: Document, session: &mut ClientSession)
+    -> Result<u64, CommonError>
+where M: MongoDao
+{
+    let collection = middleware.get_collection(collection_name)?;
+    let collection_ids = middleware.get_collection_typed::<DataCollectorRowIds>(collection_name)?;
+    let mut deleted_count = 0;
+
+    for _ in 0..FEED_EXPIRE_MAX_BATCHES {
+        // The sort is deterministic for regeneration.
+        let options = FindOptions::builder()
+            .sort(doc!{date_field: 1, "data_id": 1})
+            .limit(FEED_EXPIRE_BATCH_SIZE)
+            .projection(doc!{"data_id": 1, "feed_id": 1})
+            .build();
+        let mut cursor = collection_ids.find_with_session(filtre.clone(), options, session).await?;
+        let mut data_ids = Vec::new();
+        while cursor.advance(session).await? {
+            let row = cursor.deserialize_current()?;
+            data_ids.push(row.data_id.to_owned());
+        }
+
+        if data_ids.is_empty() {
+            break
+        }
+        let batch_full = data_ids.len() as i64 >= FEED_EXPIRE_BATCH_SIZE;
+
+        let filtre_ids = doc!{"feed_id": feed_id, "data_id": {"$in": data_ids}};
+        let result = collection.delete_many_with_session(filtre_ids, None, session).await?;
+        deleted_count += result.deleted_count;
+
+        if !batch_full {
+            break
+        }
+    }
+
+    Ok(deleted_count)
+}
@@ -1,9 +1,10 @@
 use serde::{Deserialize, Serialize};
 
+use millegrilles_common_rust::bson::Document;
 use millegrilles_common_rust::chrono::{DateTime, Utc};
 use millegrilles_common_rust::millegrilles_cryptographie::chiffrage_docs::EncryptedDocument;
 use millegrilles_common_rust::millegrilles_cryptographie::messages_structs::DechiffrageInterMillegrilleOwned;
-use millegrilles_common_rust::millegrilles_cryptographie::messages_structs::{epochseconds, epochmilliseconds, optionepochmilliseconds};
+use millegrilles_common_rust::millegrilles_cryptographie::messages_structs::{epochseconds, epochmilliseconds, optionepochseconds, optionepochmilliseconds};
 use crate::data_mongodb::{DataCollectorFilesRow, DataCollectorRow};
 
 #[derive(Serialize, Deserialize)]
@@ -81,31 +82,49 @@ impl Into<DataCollectorFilesRow> for SaveDataItemTransactionV2 {
 }
 
 #[derive(Serialize, Deserialize)]
-struct UpdateDataItem {
+pub struct SaveDataItemsTransactionV2 {
+    /// Data items to save. Duplicates and items of unknown feeds are skipped.
+    pub items: Vec<SaveDataItemTransactionV2>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UpdateDataItem {
     /// Unique data item identifier for this feed
-    id: String,
+    pub data_id: String,
     /// Source of the data item
-    feed_id: String,
-    /// Item publication or content date
+    pub feed_id: String,
+    /// Item publication or content date (V1 items)
+    #[serde(default, with="optionepochseconds", skip_serializing_if = "Option::is_none")]
+    pub pub_date: Option<DateTime<Utc>>,
+    /// Updated encrypted content of the data item (V1 items). Structure depends on the feed type.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub_date: Option<DateTime<Utc>>,
-    /// Updated encrypted content of the data item. Structure depends on the feed type.
+    pub encrypted_content: Option<EncryptedDocument>,
+    /// Replacement data file (V2 items)
     #[serde(skip_serializing_if = "Option::is_none")]
-    encrypted_content: Option<EncryptedDocument>,
+    pub data_fuuid: Option<String>,
+    /// Keys of the replacement data file (V2 items)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_ids: Option<Vec<String>>,
+    /// Item publication or content date (V2 items)
+    #[serde(default, with="optionepochmilliseconds", skip_serializing_if = "Option::is_none")]
+    pub pub_date_start: Option<DateTime<Utc>>,
+    /// Item publication or content date (V2 items)
+    #[serde(default, with="optionepochmilliseconds", skip_serializing_if = "Option::is_none")]
+    pub pub_date_end: Option<DateTime<Utc>>,
     /// Add new files associated with this data item
     #[serde(skip_serializing_if = "Option::is_none")]
-    add_files: Option<Vec<FileItem>>,
+    pub add_files: Option<Vec<FileItem>>,
     /// Fuuids of files to remove from this data item
     #[serde(skip_serializing_if = "Option::is_none")]
-    remove_files: Option<Vec<String>>,
+    pub remove_files: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize)]
-struct DeleteDataItem {
+pub struct DeleteDataItem {
     /// Feed to delete items from
-    feed_id: String,
+    pub feed_id: String,
     /// Data ids to delete
-    ids: Vec<String>,
+    pub ids: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -138,6 +157,9 @@ pub struct CreateFeedTransaction {
     pub decrypt_in_database: Option<bool>,
     /// Private information on the feed, including name/description, url, auth, etc.
     pub encrypted_feed_information: EncryptedDocument,
+    /// Limits on the data items kept for this feed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention: Option<FeedRetentionPolicy>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -158,6 +180,20 @@ pub struct UpdateFeedTransaction {
     /// Private information on the feed, including name/description, url, auth, etc.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub encrypted_feed_information: Option<EncryptedDocument>,
+    /// Limits on the data items kept for this feed. None removes the policy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention: Option<FeedRetentionPolicy>,
+}
+
+/// Retention of the data items of a feed. Older items are removed first.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FeedRetentionPolicy {
+    /// Maximum age in days. Uses the save_date of V2 items and the pub_date of V1 items.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u64>,
+    /// Maximum number of items kept for the feed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_items: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -168,6 +204,72 @@ pub struct DeleteFeedTransaction {
     pub purge: Option<bool>,
 }
 
+/// Access given to another user on a feed and its views.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedAccessPermission {
+    /// Read the feed, its data and its views.
+    Read,
+    /// Read access, plus update of the feed and management of its views.
+    Manage,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FeedAclEntry {
+    pub user_id: String,
+    pub permission: FeedAccessPermission,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GrantFeedAccessTransaction {
+    pub feed_id: String,
+    /// Grantee
+    pub user_id: String,
+    pub permission: FeedAccessPermission,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RevokeFeedAccessTransaction {
+    pub feed_id: String,
+    /// Grantee
+    pub user_id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TransferFeedTransaction {
+    pub feed_id: String,
+    /// New owner of the feed, the new owner must accept the transfer. None makes it a system feed (admin only).
+    pub user_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AcceptFeedTransferTransaction {
+    pub feed_id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PurgeUserFeedsTransaction {
+    /// Deleted user account
+    pub user_id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PurgeFeedTransaction {
+    /// Deleted feed to remove permanently. Emitted by the domain once the grace period is expired.
+    pub feed_id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExpireFeedDataTransaction {
+    pub feed_id: String,
+    /// Removes the items older than this date.
+    #[serde(default, with="optionepochmilliseconds", skip_serializing_if = "Option::is_none")]
+    pub expiration_date: Option<DateTime<Utc>>,
+    /// Removes the oldest items over this count.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_items: Option<u64>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct RestoreFeedTransaction {
     pub feed_id: String,
@@ -193,9 +295,21 @@ pub struct CreateFeedViewTransaction {
     pub name: Option<String>,
     pub active: bool,
     pub decrypted: bool,
+    /// Type of the view data (Dated, GroupedDated, Keyed, TimeSeries). Defaults to GroupedDated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_type: Option<String>,
     pub mapping_code: String,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct DeleteFeedViewTransaction {
+    pub feed_id: String,
+    pub feed_view_id: String,
+    /// If true, deletes the view permanently with its data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub purge: Option<bool>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct UpdateFeedViewTransaction {
     pub feed_id: String,
@@ -204,6 +318,9 @@ pub struct UpdateFeedViewTransaction {
     pub name: Option<String>,
     pub active: bool,
     pub decrypted: bool,
+    /// New type of the view data. The current type is kept when missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_type: Option<String>,
     pub mapping_code: String,
 }
 
@@ -223,4 +340,10 @@ pub struct FeedViewGroupedDatedItem {
     /// Files associated with this data item
     #[serde(skip_serializing_if = "Option::is_none")]
     pub files: Option<Vec<FileItem>>,
+    /// Plaintext content of the data item, only accepted for decrypted views.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decrypted_data: Option<Document>,
+    /// Plaintext numeric value, required by TimeSeries views.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
 }
\ No newline at end of file